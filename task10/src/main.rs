@@ -1,7 +1,8 @@
-use std::cmp::min;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs;
+use std::process;
 
 #[derive(Clone, PartialEq)]
 enum Direction {
@@ -31,6 +32,40 @@ impl Direction {
     }
 }
 
+fn get_symbol(first: &Direction, second: &Direction) -> char {
+    let directions = [first, second];
+    let has = |direction: Direction| directions.contains(&&direction);
+    if has(Direction::North) && has(Direction::South) {
+        '|'
+    } else if has(Direction::West) && has(Direction::East) {
+        '-'
+    } else if has(Direction::North) && has(Direction::East) {
+        'L'
+    } else if has(Direction::North) && has(Direction::West) {
+        'J'
+    } else if has(Direction::South) && has(Direction::West) {
+        '7'
+    } else {
+        'F'
+    }
+}
+
+enum StartError {
+    Missing,
+    NoLoop,
+    Ambiguous(Vec<char>)
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StartError::Missing => write!(f, "there's no starting position 'S' in the grid"),
+            StartError::NoLoop => write!(f, "no loop goes through the starting position"),
+            StartError::Ambiguous(symbols) => write!(f, "the starting position could be any of {:?}", symbols)
+        }
+    }
+}
+
 #[derive(Clone)]
 struct GridField {
     connected_directions: Vec<Direction>,
@@ -38,59 +73,48 @@ struct GridField {
 }
 
 impl GridField {
-    fn new(grid: &Vec<Vec<char>>, coordinates: &(usize, usize)) -> Self {
+    fn new(grid: &[Vec<char>], coordinates: &(usize, usize)) -> Self {
         let symbol = get_char(grid, coordinates);
         match symbol {
             '.' => Self {
                 connected_directions: vec![],
-                coordinates: coordinates.clone()
+                coordinates: *coordinates
             },
             '-' => Self {
                 connected_directions: vec![Direction::West, Direction::East],
-                coordinates: coordinates.clone()
+                coordinates: *coordinates
             },
             '|' => Self {
                 connected_directions: vec![Direction::North, Direction::South],
-                coordinates: coordinates.clone()
+                coordinates: *coordinates
             },
             'L' => Self {
                 connected_directions: vec![Direction::North, Direction::East],
-                coordinates: coordinates.clone()
+                coordinates: *coordinates
             },
             'J' => Self {
                 connected_directions: vec![Direction::North, Direction::West],
-                coordinates: coordinates.clone()
+                coordinates: *coordinates
             },
             '7' => Self {
                 connected_directions: vec![Direction::South, Direction::West],
-                coordinates: coordinates.clone()
+                coordinates: *coordinates
             },
             'F' => Self {
                 connected_directions: vec![Direction::South, Direction::East],
-                coordinates: coordinates.clone()
-            },
-            'S' => Self {
-                connected_directions: vec![Direction::North, Direction::South, Direction::West, Direction::East],
-                coordinates: coordinates.clone()
+                coordinates: *coordinates
             },
             _ => panic!("Unexpected symbol")
         }
     }
 
-    fn create(connected_directions: Vec<Direction>, coordinates: (usize, usize)) -> Self {
-        Self {
-            connected_directions,
-            coordinates
-        }
-    }
-
     fn go_from(&self, from: &Direction) -> (Direction, (usize, usize)) { // returns next from and coordinates
         let to = self.connected_directions.iter().find(|direction| direction != &from ).unwrap();
         (to.opposite(), to.go(&self.coordinates))
     }
 }
 
-fn get_char(grid: &Vec<Vec<char>>, coordinates: &(usize, usize)) -> char {
+fn get_char(grid: &[Vec<char>], coordinates: &(usize, usize)) -> char {
     if coordinates.0 >= grid.len() || coordinates.1 >= grid[coordinates.0].len() {
         '.'
     } else {
@@ -98,129 +122,257 @@ fn get_char(grid: &Vec<Vec<char>>, coordinates: &(usize, usize)) -> char {
     }
 }
 
-fn get_nest_candidates(path: &Vec<GridField>) -> HashSet<(usize, usize)> {
-    let mut result = HashSet::new();
-    for path_field in path {
-        result.extend(get_neighbours(&path_field.coordinates));
+fn parse_input(input: &str) -> Vec<Vec<char>> {
+    let mut result = vec![];
+    for line in input.lines() {
+        result.push(line.chars().collect())
     }
     result
 }
 
-fn get_neighbours(coordinates: &(usize, usize)) -> HashSet<(usize, usize)> {
-    HashSet::from_iter(vec![
-        (coordinates.0.overflowing_sub(1).0, coordinates.1),
-        (coordinates.0 + 1, coordinates.1),
-        (coordinates.0, coordinates.1.overflowing_sub(1).0),
-        (coordinates.0, coordinates.1 + 1),
-    ].into_iter())
+// Twice the area enclosed by the loop, going through the centres of its tiles
+fn get_doubled_shoelace_area(path: &[GridField]) -> i64 {
+    let mut doubled_area = 0;
+    for (i, field) in path.iter().enumerate() {
+        let next = &path[(i + 1) % path.len()];
+        doubled_area += field.coordinates.0 as i64 * next.coordinates.1 as i64 - next.coordinates.0 as i64 * field.coordinates.1 as i64;
+    }
+    doubled_area.abs()
 }
 
-fn parse_input(input: &str) -> Vec<Vec<char>> {
-    let mut result = vec![];
-    for line in input.lines() {
-        result.push(line.chars().collect())
+// Pick's theorem, area = interior + boundary / 2 - 1, with every loop tile a boundary point
+fn get_loop_area(path: &[GridField]) -> usize {
+    ((get_doubled_shoelace_area(path) - path.len() as i64) / 2 + 1) as usize
+}
+
+// Scanline parity, a tile is inside when there's an odd number of loop tiles
+// connected to the north on its west side
+fn get_interior_tiles(grid: &[Vec<char>], path: &[GridField]) -> HashSet<(usize, usize)> {
+    let mut north_connected = HashSet::new();
+    let mut loop_tiles = HashSet::new();
+    for field in path {
+        loop_tiles.insert(field.coordinates);
+        if field.connected_directions.contains(&Direction::North) {
+            north_connected.insert(field.coordinates);
+        }
+    }
+    let mut result = HashSet::new();
+    for (i, row) in grid.iter().enumerate() {
+        let mut inside = false;
+        for j in 0..row.len() {
+            if north_connected.contains(&(i, j)) {
+                inside = !inside;
+            } else if inside && !loop_tiles.contains(&(i, j)) {
+                result.insert((i, j));
+            }
+        }
     }
     result
 }
 
-fn check_if_nest_horizontally(coordinates: &(usize, usize), path: &Vec<GridField>) -> bool {
-    let mut north_connections_to_west = 0;
-    let mut south_connections_to_west = 0;
-    for path_element in path {
-        if path_element.coordinates.0 == coordinates.0 && path_element.coordinates.1 <  coordinates.1 {
-            if path_element.connected_directions.contains(&Direction::North) {
-                north_connections_to_west += 1;
-            }
-            if path_element.connected_directions.contains(&Direction::South) {
-                south_connections_to_west += 1;
-            }
+fn find_loop(grid: &[Vec<char>], start: &(usize, usize)) -> Option<Vec<GridField>> { // the starting field is the last one
+    let starting_field = GridField::new(grid, start);
+    let initial_direction = &starting_field.connected_directions[0];
+    let mut comming_from = initial_direction.opposite();
+    let mut current_location = initial_direction.go(start);
+    let mut path_elements = vec![];
+    while current_location != *start {
+        let current_field = GridField::new(grid, &current_location);
+        if !current_field.connected_directions.contains(&comming_from) {
+            return None;
         }
+        path_elements.push(current_field.clone());
+        (comming_from, current_location) = current_field.go_from(&comming_from);
+    }
+    if !starting_field.connected_directions.contains(&comming_from) {
+        return None;
     }
-    let north_south_connections_to_west = min(north_connections_to_west, south_connections_to_west);
-    north_south_connections_to_west % 2 == 1
+    path_elements.push(starting_field);
+    Some(path_elements)
 }
 
-fn solve_part_1(grid: &Vec<Vec<char>>) -> u32 {
-    let mut starting_position = (0, 0);
+// Replaces 'S' with the only pipe closing a loop through it and returns its coordinates
+fn resolve_start(grid: &mut [Vec<char>]) -> Result<(usize, usize), StartError> {
+    let mut start = None;
     for (i, row) in grid.iter().enumerate() {
         if let Some(j) = row.iter().position(|c| *c == 'S') {
-            starting_position = (i, j);
+            start = Some((i, j));
             break;
         }
     }
-    let directions_to_search = vec![Direction::North, Direction::South, Direction::West, Direction::East];
-    'a: for initial_direction in directions_to_search {
-        let mut path_length = 2;
-        let mut comming_from = initial_direction.opposite();
-        let mut current_location = initial_direction.go(&starting_position);
-        while get_char(grid, &current_location) != 'S' {
-            let current_field = GridField::new(grid, &current_location);
-            if !current_field.connected_directions.contains(&comming_from) {
-                continue 'a;
+    let start = start.ok_or(StartError::Missing)?;
+    // only the neighbours pointing back at the start can be connected to it
+    let all_directions = [Direction::North, Direction::South, Direction::West, Direction::East];
+    let connected: Vec<&Direction> = all_directions.iter().filter(|direction| {
+        let neighbour = direction.go(&start);
+        get_char(grid, &neighbour) != 'S' && GridField::new(grid, &neighbour).connected_directions.contains(&direction.opposite())
+    }).collect();
+    let mut symbols = vec![];
+    for (i, first) in connected.iter().enumerate() {
+        for second in connected.iter().skip(i + 1) {
+            let symbol = get_symbol(first, second);
+            grid[start.0][start.1] = symbol;
+            if find_loop(grid, &start).is_some() {
+                symbols.push(symbol);
             }
-            (comming_from, current_location) = current_field.go_from(&comming_from);
-            path_length += 1;
         }
-        return path_length / 2;
     }
-    0
+    match symbols.len() {
+        0 => {
+            grid[start.0][start.1] = 'S';
+            Err(StartError::NoLoop)
+        }
+        1 => {
+            grid[start.0][start.1] = symbols[0];
+            Ok(start)
+        }
+        _ => {
+            grid[start.0][start.1] = 'S';
+            Err(StartError::Ambiguous(symbols))
+        }
+    }
 }
 
-fn solve_part_2(grid: &Vec<Vec<char>>) -> u32 {
-    let mut starting_position = (0, 0);
-    for (i, row) in grid.iter().enumerate() {
-        if let Some(j) = row.iter().position(|c| *c == 'S') {
-            starting_position = (i, j);
-            break;
-        }
+fn solve_part_1(grid: &[Vec<char>], start: &(usize, usize)) -> usize {
+    find_loop(grid, start).unwrap().len() / 2
+}
+
+fn solve_part_2(grid: &[Vec<char>], start: &(usize, usize)) -> usize {
+    get_loop_area(&find_loop(grid, start).unwrap())
+}
+
+fn solve_part_2_scanline(grid: &[Vec<char>], start: &(usize, usize)) -> usize {
+    get_interior_tiles(grid, &find_loop(grid, start).unwrap()).len()
+}
+
+// Heavy box-drawing character for a pipe of the main loop
+fn get_box_char(connected_directions: &[Direction]) -> char {
+    match get_symbol(&connected_directions[0], &connected_directions[1]) {
+        '|' => '┃',
+        '-' => '━',
+        'L' => '┗',
+        'J' => '┛',
+        '7' => '┓',
+        _ => '┏'
     }
-    let directions_to_search = vec![Direction::North, Direction::South, Direction::West, Direction::East];
-    let mut path_elements = vec![];
-    'a: for initial_direction in directions_to_search {
-        let mut comming_from = initial_direction.opposite();
-        let mut current_location = initial_direction.go(&starting_position);
-        path_elements.clear();
-        while get_char(grid, &current_location) != 'S' {
-            let current_field = GridField::new(grid, &current_location);
-            if !current_field.connected_directions.contains(&comming_from) {
-                continue 'a;
-            }
-            path_elements.push(current_field.clone());
-            (comming_from, current_location) = current_field.go_from(&comming_from);
-        }
-        path_elements.push(GridField::create(vec![initial_direction, comming_from], starting_position) );
-        break;
-    }
-
-    let mut nest_candidates = get_nest_candidates(&path_elements);
-    let mut checked_fields = HashSet::from_iter(path_elements.iter().map(|path_element| path_element.coordinates));
-    let mut possible_nest_locations = 0;
-    nest_candidates = nest_candidates.difference(&checked_fields).map(|element| *element).collect();
-    while !nest_candidates.is_empty() {
-        let mut new_nest_candiadtes = HashSet::new();
-        for candidate in &nest_candidates {
-            if check_if_nest_horizontally(&candidate, &path_elements) {
-                possible_nest_locations += 1;
-                new_nest_candiadtes.extend(get_neighbours(&candidate));
-                checked_fields.insert(*candidate);
+}
+
+enum TileKind {
+    Loop(char),
+    Inside,
+    Outside
+}
+
+// Every tile of the grid, pipes which aren't part of the main loop count as ground
+fn get_tile_kinds(grid: &[Vec<char>], start: &(usize, usize)) -> Vec<Vec<TileKind>> {
+    let path = find_loop(grid, start).unwrap();
+    let interior = get_interior_tiles(grid, &path);
+    let loop_fields: HashMap<(usize, usize), &GridField> = path.iter().map(|field| (field.coordinates, field)).collect();
+    let mut result = vec![];
+    for (i, row) in grid.iter().enumerate() {
+        result.push((0..row.len()).map(|j| {
+            if let Some(field) = loop_fields.get(&(i, j)) {
+                TileKind::Loop(get_box_char(&field.connected_directions))
+            } else if interior.contains(&(i, j)) {
+                TileKind::Inside
+            } else {
+                TileKind::Outside
             }
+        }).collect());
+    }
+    result
+}
+
+fn render_ansi(grid: &[Vec<char>], start: &(usize, usize)) -> String {
+    let mut result = String::new();
+    for row in get_tile_kinds(grid, start) {
+        for tile in row {
+            result += &match tile {
+                TileKind::Loop(symbol) => format!("\x1b[1;33m{}\x1b[0m", symbol),
+                TileKind::Inside => String::from("\x1b[1;32m●\x1b[0m"),
+                TileKind::Outside => String::from("\x1b[2m·\x1b[0m")
+            };
         }
-        nest_candidates = new_nest_candiadtes.difference(&checked_fields).map(|element| *element).collect();
+        result += "\n";
     }
+    result
+}
 
-    possible_nest_locations
+fn render_html(grid: &[Vec<char>], start: &(usize, usize)) -> String {
+    let mut result = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>\n");
+    result += "pre { line-height: 1; font-size: 12px; background: #111; color: #444; }\n";
+    result += ".loop { color: #fc3; font-weight: bold; }\n.inside { color: #3c3; }\n";
+    result += "</style>\n</head>\n<body>\n<pre>\n";
+    for row in get_tile_kinds(grid, start) {
+        for tile in row {
+            result += &match tile {
+                TileKind::Loop(symbol) => format!("<span class=\"loop\">{}</span>", symbol),
+                TileKind::Inside => String::from("<span class=\"inside\">●</span>"),
+                TileKind::Outside => String::from("·")
+            };
+        }
+        result += "\n";
+    }
+    result += "</pre>\n</body>\n</html>\n";
+    result
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let task_part = args[1].parse::<u64>().unwrap();
     let file_path = &args[2];
     let file_contents = fs::read_to_string(file_path).unwrap();
-    let grid = parse_input(&file_contents);
-    let result = match task_part {
-        1 => solve_part_1(&grid),
-        2 => solve_part_2(&grid),
+    let mut grid = parse_input(&file_contents);
+    let start = match resolve_start(&mut grid) {
+        Ok(start) => start,
+        Err(error) => {
+            eprintln!("Invalid grid: {}", error);
+            process::exit(1);
+        }
+    };
+    let result = match args[1].as_str() {
+        "render" => {
+            print!("{}", render_ansi(&grid, &start));
+            return;
+        }
+        "html" => {
+            print!("{}", render_html(&grid, &start));
+            return;
+        }
+        "1" => solve_part_1(&grid, &start),
+        "2" => solve_part_2(&grid, &start),
+        "scanline" => solve_part_2_scanline(&grid, &start),
+        "check" => {
+            let shoelace = solve_part_2(&grid, &start);
+            let scanline = solve_part_2_scanline(&grid, &start);
+            if shoelace != scanline {
+                eprintln!("Mismatch: shoelace and Pick's theorem give {}, scanline parity gives {}", shoelace, scanline);
+                process::exit(1);
+            }
+            shoelace
+        }
         _ => 0
     };
     println!("{}", result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shoelace_and_scanline_agree_on_examples() {
+        let examples = [
+            (include_str!("../example1.txt"), 1),
+            (include_str!("../example2.txt"), 1),
+            (include_str!("../example3.txt"), 4),
+            (include_str!("../example4.txt"), 8),
+            (include_str!("../example5.txt"), 10)
+        ];
+        for (input, nest_tiles) in examples {
+            let mut grid = parse_input(input);
+            let start = resolve_start(&mut grid).ok().unwrap();
+            assert_eq!(solve_part_2(&grid, &start), nest_tiles);
+            assert_eq!(solve_part_2_scanline(&grid, &start), nest_tiles);
+        }
+    }
+}
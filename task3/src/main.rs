@@ -1,38 +1,100 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
+use std::fmt;
 use std::fs;
+use std::process;
+
+struct PartNumber {
+    value: i32,
+    start: (i32, i32),
+    end: (i32, i32), // coordinates of the last digit
+    adjacent_symbols: Vec<(char, (i32, i32))>
+}
+
+enum SchematicError {
+    Empty,
+    InvalidCharacter { x: usize, y: usize, character: char },
+    NumberTooLarge { x: usize, y: usize },
+    RaggedRowWithWrap { y: usize, width: usize, expected_width: usize }
+}
+
+impl fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchematicError::Empty => write!(f, "schematic is empty"),
+            SchematicError::InvalidCharacter { x, y, character } => write!(f, "invalid character {:?} at ({}, {})", character, x, y),
+            SchematicError::NumberTooLarge { x, y } => write!(f, "number starting at ({}, {}) does not fit in i32", x, y),
+            SchematicError::RaggedRowWithWrap { y, width, expected_width } =>
+                write!(f, "row {} has width {} but wrap-around requires every row to have width {}", y, width, expected_width)
+        }
+    }
+}
 
 struct EngineGrid {
     grid: Vec<Vec<char>>,
-    width: i32,
-    height: i32
+    height: i32,
+    wrap: bool // neighbours wrap around the edges, like on a torus
 }
 
 impl EngineGrid {
-    fn new(input: &str) -> Self {
+    fn new(input: &str, wrap: bool) -> Result<Self, SchematicError> {
         let mut grid: Vec<Vec<char>> = vec![];
-        for line in input.lines() {
-            grid.push(line.chars().collect());
+        for (y, line) in input.lines().enumerate() {
+            let row: Vec<char> = line.chars().collect();
+            if let Some(x) = row.iter().position(|c| !c.is_ascii_graphic()) {
+                return Err(SchematicError::InvalidCharacter { x, y, character: row[x] });
+            }
+            grid.push(row);
+        }
+        if grid.iter().all(|row| row.is_empty()) {
+            return Err(SchematicError::Empty);
+        }
+        if wrap {
+            let expected_width = grid[0].len();
+            if let Some(y) = grid.iter().position(|row| row.len() != expected_width) {
+                return Err(SchematicError::RaggedRowWithWrap { y, width: grid[y].len(), expected_width });
+            }
         }
-        // there is an assumption that the grid is square
-        let width = grid[0].len() as i32;
         let height = grid.len() as i32;
-        EngineGrid {
+        let engine_grid = EngineGrid {
             grid,
-            width,
-            height
+            height,
+            wrap
+        };
+        for y in 0..engine_grid.height {
+            for x in 0..engine_grid.row_width(y) {
+                if engine_grid.grid[y as usize][x as usize].is_numeric() && engine_grid.get_number_start(x, y) == (x, y) {
+                    let (ending_x, _) = engine_grid.get_number_end(x, y);
+                    let number_str: String = engine_grid.grid[y as usize][x as usize..=ending_x as usize].iter().collect();
+                    if number_str.parse::<i32>().is_err() {
+                        return Err(SchematicError::NumberTooLarge { x: x as usize, y: y as usize });
+                    }
+                }
+            }
         }
+        Ok(engine_grid)
+    }
+
+    fn row_width(&self, y: i32) -> i32 {
+        self.grid[y as usize].len() as i32
     }
 
     fn is_inside_grid(&self, x: i32, y: i32) -> bool {
-        x >= 0 && x < self.width && y >= 0 && y < self.height
+        y >= 0 && y < self.height && x >= 0 && x < self.row_width(y)
     }
 
     fn get_neighbour_coordinates(&self, x: i32, y:i32) -> Vec<(i32, i32)> {
         let mut result = vec![];
         let neighbour_candidates = vec![(x-1, y-1), (x, y-1), (x+1, y-1), (x+1, y), (x+1, y+1), (x, y+1), (x-1, y+1), (x-1, y)];
         for neighbour_candidate in neighbour_candidates {
-            if self.is_inside_grid(neighbour_candidate.0, neighbour_candidate.1) {
+            let neighbour_candidate = if self.wrap {
+                // rows all have the same width when wrapping, checked in new
+                (neighbour_candidate.0.rem_euclid(self.row_width(0)), neighbour_candidate.1.rem_euclid(self.height))
+            } else {
+                neighbour_candidate
+            };
+            // on small wrapped grids a cell can reach itself or the same neighbour twice
+            if self.is_inside_grid(neighbour_candidate.0, neighbour_candidate.1) && neighbour_candidate != (x, y) && !result.contains(&neighbour_candidate) {
                 result.push(neighbour_candidate);
             }
         }
@@ -47,25 +109,212 @@ impl EngineGrid {
         (starting_x, y)
     }
 
-    fn get_number(&self, x: i32, y: i32) -> i32 {
-        let mut ending_x = x + 1;
-        while self.is_inside_grid(ending_x, y) && self.grid[y as usize][ending_x as usize].is_numeric() {
+    fn get_number_end(&self, x: i32, y: i32) -> (i32, i32) {
+        let mut ending_x = x;
+        while self.is_inside_grid(ending_x + 1, y) && self.grid[y as usize][(ending_x + 1) as usize].is_numeric() {
             ending_x += 1;
         }
-        let number_str: String = self.grid[y as usize][x as usize..ending_x as usize].iter().collect();
+        (ending_x, y)
+    }
+
+    fn get_number(&self, x: i32, y: i32) -> i32 {
+        let (ending_x, _) = self.get_number_end(x, y);
+        let number_str: String = self.grid[y as usize][x as usize..=ending_x as usize].iter().collect();
         number_str.parse::<i32>().unwrap()
     }
+
+    fn get_adjacent_symbols(&self, start: (i32, i32), end: (i32, i32)) -> Vec<(char, (i32, i32))> {
+        let mut symbol_coordinates = HashSet::<(i32, i32)>::new();
+        for x in start.0..=end.0 {
+            for (x_neighbour, y_neighbour) in self.get_neighbour_coordinates(x, start.1) {
+                if is_symbol(self.grid[y_neighbour as usize][x_neighbour as usize]) {
+                    symbol_coordinates.insert((x_neighbour, y_neighbour));
+                }
+            }
+        }
+        let mut symbol_coordinates: Vec<(i32, i32)> = symbol_coordinates.into_iter().collect();
+        symbol_coordinates.sort_by_key(|&(x, y)| (y, x));
+        symbol_coordinates.into_iter().map(|(x, y)| (self.grid[y as usize][x as usize], (x, y))).collect()
+    }
+
+    fn get_part_catalog(&self) -> Vec<PartNumber> {
+        let mut catalog = vec![];
+        for y in 0..self.height {
+            for x in 0..self.row_width(y) {
+                if self.grid[y as usize][x as usize].is_numeric() && self.get_number_start(x, y) == (x, y) {
+                    let end = self.get_number_end(x, y);
+                    catalog.push(PartNumber {
+                        value: self.get_number(x, y),
+                        start: (x, y),
+                        end,
+                        adjacent_symbols: self.get_adjacent_symbols((x, y), end)
+                    });
+                }
+            }
+        }
+        catalog
+    }
+
+    fn build_graph(&self) -> SchematicGraph {
+        let mut symbols = vec![];
+        let mut symbol_indices = HashMap::<(i32, i32), usize>::new();
+        for y in 0..self.height {
+            for x in 0..self.row_width(y) {
+                if is_symbol(self.grid[y as usize][x as usize]) {
+                    symbol_indices.insert((x, y), symbols.len());
+                    symbols.push((self.grid[y as usize][x as usize], (x, y)));
+                }
+            }
+        }
+        let parts = self.get_part_catalog();
+        let mut part_to_symbols = vec![vec![]; parts.len()];
+        let mut symbol_to_parts = vec![vec![]; symbols.len()];
+        for (part_index, part) in parts.iter().enumerate() {
+            for (_, position) in &part.adjacent_symbols {
+                let symbol_index = symbol_indices[position];
+                part_to_symbols[part_index].push(symbol_index);
+                symbol_to_parts[symbol_index].push(part_index);
+            }
+        }
+        SchematicGraph {
+            parts,
+            symbols,
+            part_to_symbols,
+            symbol_to_parts
+        }
+    }
+}
+
+// bipartite graph, parts are only connected to symbols and symbols only to parts
+struct SchematicGraph {
+    parts: Vec<PartNumber>,
+    symbols: Vec<(char, (i32, i32))>,
+    part_to_symbols: Vec<Vec<usize>>, // part index to indices of adjacent symbols
+    symbol_to_parts: Vec<Vec<usize>> // symbol index to indices of adjacent parts
+}
+
+impl SchematicGraph {
+    // parts linked through shared symbols, parts without symbols form their own components
+    fn get_part_components(&self) -> Vec<Vec<usize>> {
+        let mut components = vec![];
+        let mut visited = vec![false; self.parts.len()];
+        for first_part in 0..self.parts.len() {
+            if visited[first_part] {
+                continue;
+            }
+            visited[first_part] = true;
+            let mut component = vec![];
+            let mut to_visit = VecDeque::from([first_part]);
+            while let Some(part) = to_visit.pop_front() {
+                component.push(part);
+                for &symbol in &self.part_to_symbols[part] {
+                    for &next_part in &self.symbol_to_parts[symbol] {
+                        if !visited[next_part] {
+                            visited[next_part] = true;
+                            to_visit.push_back(next_part);
+                        }
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    fn get_symbols_without_parts(&self) -> Vec<usize> {
+        (0..self.symbols.len()).filter(|&symbol| self.symbol_to_parts[symbol].is_empty()).collect()
+    }
+
+    fn get_parts_with_multiple_symbols(&self) -> Vec<usize> {
+        (0..self.parts.len()).filter(|&part| self.part_to_symbols[part].len() > 1).collect()
+    }
+
+    fn describe_part(&self, part: usize) -> String {
+        format!("{} ({}, {})", self.parts[part].value, self.parts[part].start.0, self.parts[part].start.1)
+    }
+
+    fn describe_symbol(&self, symbol: usize) -> String {
+        let (character, (x, y)) = self.symbols[symbol];
+        format!("{} ({}, {})", character, x, y)
+    }
+
+    fn to_dot(&self) -> String {
+        let mut result = String::from("graph schematic {\n");
+        for part in 0..self.parts.len() {
+            result.push_str(&format!("    p{} [label=\"{}\", shape=box];\n", part, escape_dot(&self.describe_part(part))));
+        }
+        for symbol in 0..self.symbols.len() {
+            result.push_str(&format!("    s{} [label=\"{}\", shape=circle];\n", symbol, escape_dot(&self.describe_symbol(symbol))));
+        }
+        for (part, symbols) in self.part_to_symbols.iter().enumerate() {
+            for symbol in symbols {
+                result.push_str(&format!("    p{} -- s{};\n", part, symbol));
+            }
+        }
+        result.push_str("}\n");
+        result
+    }
 }
 
 fn is_symbol(c: char) -> bool {
     !c.is_numeric() && c != '.'
 }
 
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+    result
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn catalog_to_csv(catalog: &[PartNumber]) -> String {
+    let mut result = String::from("value,start_x,start_y,end_x,end_y,symbols\n");
+    for part in catalog {
+        // symbols are written as symbol@x:y separated by semicolons
+        let symbols: Vec<String> = part.adjacent_symbols.iter()
+            .map(|(symbol, (x, y))| format!("{}@{}:{}", symbol, x, y))
+            .collect();
+        result.push_str(&format!("{},{},{},{},{},{}\n", part.value, part.start.0, part.start.1, part.end.0, part.end.1, escape_csv(&symbols.join(";"))));
+    }
+    result
+}
+
+fn catalog_to_json(catalog: &[PartNumber]) -> String {
+    let mut parts = vec![];
+    for part in catalog {
+        let symbols: Vec<String> = part.adjacent_symbols.iter()
+            .map(|(symbol, (x, y))| format!("{{\"symbol\":\"{}\",\"x\":{},\"y\":{}}}", escape_json(&symbol.to_string()), x, y))
+            .collect();
+        parts.push(format!("{{\"value\":{},\"start\":{{\"x\":{},\"y\":{}}},\"end\":{{\"x\":{},\"y\":{}}},\"symbols\":[{}]}}",
+            part.value, part.start.0, part.start.1, part.end.0, part.end.1, symbols.join(",")));
+    }
+    format!("[{}]", parts.join(","))
+}
+
 fn solve_part_1(grid: &EngineGrid) -> i32 {
     let mut result = 0;
     let mut number_coordinates = HashSet::<(i32, i32)>::new();
     for y in 0..grid.height {
-        for x in 0..grid.width {
+        for x in 0..grid.row_width(y) {
             if is_symbol(grid.grid[y as usize][x as usize]) {
                 let neighbours = grid.get_neighbour_coordinates(x, y);
                 for (x_neighbour, y_neighbour) in neighbours {
@@ -82,41 +331,159 @@ fn solve_part_1(grid: &EngineGrid) -> i32 {
     result
 }
 
-fn solve_part_2(grid: &EngineGrid) -> i32 {
-    let mut result = 0;
+enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    Between(usize, usize) // inclusive on both ends
+}
+
+impl Arity {
+    fn new(input: &str) -> Self {
+        if let Some(minimum) = input.strip_suffix('+') {
+            Arity::AtLeast(minimum.parse().unwrap())
+        } else if let Some((minimum, maximum)) = input.split_once('-') {
+            Arity::Between(minimum.parse().unwrap(), maximum.parse().unwrap())
+        } else {
+            Arity::Exact(input.parse().unwrap())
+        }
+    }
+
+    fn accepts(&self, parts_count: usize) -> bool {
+        match self {
+            Arity::Exact(count) => parts_count == *count,
+            Arity::AtLeast(minimum) => parts_count >= *minimum,
+            Arity::Between(minimum, maximum) => parts_count >= *minimum && parts_count <= *maximum
+        }
+    }
+}
+
+enum GearOperation {
+    Product,
+    Sum,
+    Max
+}
+
+impl GearOperation {
+    fn new(input: &str) -> Self {
+        match input {
+            "product" => GearOperation::Product,
+            "sum" => GearOperation::Sum,
+            "max" => GearOperation::Max,
+            _ => panic!("Unknown gear operation")
+        }
+    }
+
+    fn combine(&self, parts: &[i32]) -> i64 {
+        let parts = parts.iter().map(|&part| part as i64);
+        match self {
+            GearOperation::Product => parts.product(),
+            GearOperation::Sum => parts.sum(),
+            GearOperation::Max => parts.max().unwrap_or(0)
+        }
+    }
+}
+
+struct GearRule {
+    symbol: char,
+    arity: Arity,
+    operation: GearOperation
+}
+
+struct GearCandidate {
+    symbol: char,
+    position: (i32, i32),
+    parts: Vec<i32>
+}
+
+fn get_gear_candidates(grid: &EngineGrid, symbol: char) -> Vec<GearCandidate> {
+    let mut parts_by_symbol = HashMap::<(i32, i32), Vec<i32>>::new();
+    for part in grid.get_part_catalog() {
+        for (_, position) in part.adjacent_symbols {
+            parts_by_symbol.entry(position).or_default().push(part.value);
+        }
+    }
+    let mut candidates = vec![];
     for y in 0..grid.height {
-        for x in 0..grid.width {
-            if grid.grid[y as usize][x as usize] == '*' {
-                let neighbours = grid.get_neighbour_coordinates(x, y);
-                let mut number_coordinates = HashSet::<(i32, i32)>::new();
-                for (x_neighbour, y_neighbour) in neighbours {
-                    if grid.grid[y_neighbour as usize][x_neighbour as usize].is_numeric() {
-                        number_coordinates.insert(grid.get_number_start(x_neighbour, y_neighbour));
-                    }
-                }
-                if number_coordinates.len() == 2 {
-                    let mut gear_ratio = 1;
-                    for (x_number, y_number) in number_coordinates {
-                        gear_ratio *= grid.get_number(x_number, y_number);
-                    }
-                    result += gear_ratio;
-                }
+        for x in 0..grid.row_width(y) {
+            if grid.grid[y as usize][x as usize] == symbol {
+                candidates.push(GearCandidate {
+                    symbol,
+                    position: (x, y),
+                    parts: parts_by_symbol.remove(&(x, y)).unwrap_or_default()
+                });
             }
         }
     }
-    result
+    candidates
+}
+
+fn solve_gears(grid: &EngineGrid, rule: &GearRule) -> i64 {
+    get_gear_candidates(grid, rule.symbol).iter()
+        .filter(|candidate| rule.arity.accepts(candidate.parts.len()))
+        .map(|candidate| rule.operation.combine(&candidate.parts))
+        .sum()
+}
+
+fn get_near_miss_gears(grid: &EngineGrid, rule: &GearRule) -> Vec<GearCandidate> {
+    get_gear_candidates(grid, rule.symbol).into_iter()
+        .filter(|candidate| !rule.arity.accepts(candidate.parts.len()))
+        .collect()
+}
+
+fn solve_part_2(grid: &EngineGrid) -> i64 {
+    solve_gears(grid, &GearRule { symbol: '*', arity: Arity::Exact(2), operation: GearOperation::Product })
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let task_part = args[1].parse::<i32>().unwrap();
-    let file_path = &args[2];
+    let wrap = args.iter().any(|arg| arg == "--wrap");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--wrap").collect();
+    let file_path = args[2];
     let file_contents = fs::read_to_string(file_path).unwrap();
-    let grid = EngineGrid::new(&file_contents);
-    let result = match task_part {
-        1 => solve_part_1(&grid),
-        2 => solve_part_2(&grid),
-        _ => -1
+    let grid = match EngineGrid::new(&file_contents, wrap) {
+        Ok(grid) => grid,
+        Err(error) => {
+            eprintln!("Invalid schematic: {}", error);
+            process::exit(1);
+        }
     };
-    println!("{}", result);
+    match args[1].as_str() {
+        "csv" => print!("{}", catalog_to_csv(&grid.get_part_catalog())),
+        "json" => println!("{}", catalog_to_json(&grid.get_part_catalog())),
+        "dot" => print!("{}", grid.build_graph().to_dot()),
+        "graph" => {
+            let graph = grid.build_graph();
+            for component in graph.get_part_components() {
+                let parts: Vec<String> = component.iter().map(|&part| graph.describe_part(part)).collect();
+                println!("component: {}", parts.join(", "));
+            }
+            for symbol in graph.get_symbols_without_parts() {
+                println!("symbol without parts: {}", graph.describe_symbol(symbol));
+            }
+            for part in graph.get_parts_with_multiple_symbols() {
+                let symbols: Vec<String> = graph.part_to_symbols[part].iter().map(|&symbol| graph.describe_symbol(symbol)).collect();
+                println!("part touching multiple symbols: {} -> {}", graph.describe_part(part), symbols.join(", "));
+            }
+        }
+        "gears" => {
+            // usage: gears <file> [symbol] [arity: N, N+ or N-M] [product|sum|max]
+            let rule = GearRule {
+                symbol: args.get(3).map_or('*', |symbol| symbol.chars().next().unwrap()),
+                arity: Arity::new(args.get(4).map_or("2", |arity| arity.as_str())),
+                operation: GearOperation::new(args.get(5).map_or("product", |operation| operation.as_str()))
+            };
+            println!("{}", solve_gears(&grid, &rule));
+            for near_miss in get_near_miss_gears(&grid, &rule) {
+                println!("near miss '{}' at ({}, {}) with {} parts: {:?}", near_miss.symbol, near_miss.position.0, near_miss.position.1, near_miss.parts.len(), near_miss.parts);
+            }
+        }
+        task_part => {
+            let result = match task_part.parse::<i32>().unwrap() {
+                1 => solve_part_1(&grid) as i64,
+                2 => solve_part_2(&grid),
+                _ => -1
+            };
+            println!("{}", result);
+        }
+    }
 }
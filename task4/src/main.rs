@@ -1,19 +1,21 @@
-use std::collections::HashMap;
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs;
+use std::process;
 
 struct Scratchcard {
     winning_numbers: Vec<i32>,
     own_numbers: Vec<i32>,
-    _card_number: i32
+    card_number: i32
 }
 
 impl Scratchcard {
     fn new(input: &str) -> Self {
         let (card_number_part, numbers_part) = input.split_once(": ").unwrap();
         let (_, card_number_str) = card_number_part.split_once(' ').unwrap();
-        let _card_number = card_number_str.trim().parse::<i32>().unwrap();
+        let card_number = card_number_str.trim().parse::<i32>().unwrap();
         let (winning_set, own_set) = numbers_part.split_once(" | ").unwrap();
         let mut winning_numbers = vec![];
         let mut own_numbers = vec![];
@@ -32,7 +34,7 @@ impl Scratchcard {
         Self {
             winning_numbers,
             own_numbers,
-            _card_number
+            card_number
         }
     }
 
@@ -41,53 +43,276 @@ impl Scratchcard {
         let winning_numbers_set: HashSet<i32> = HashSet::from_iter(self.winning_numbers.iter().copied());
         own_numbers_set.intersection(&winning_numbers_set).copied().collect()
     }
+
+    // every copy of a number counts, as many times as it appears on both sides
+    fn get_matching_numbers_multiset(&self) -> Vec<i32> {
+        let mut winning_numbers_left = HashMap::<i32, usize>::new();
+        for number in &self.winning_numbers {
+            *winning_numbers_left.entry(*number).or_insert(0) += 1;
+        }
+        let mut result = vec![];
+        for number in &self.own_numbers {
+            if let Some(count) = winning_numbers_left.get_mut(number) {
+                if *count > 0 {
+                    *count -= 1;
+                    result.push(*number);
+                }
+            }
+        }
+        result
+    }
+
+    fn get_matching_count(&self, mode: &MatchingMode) -> usize {
+        match mode {
+            MatchingMode::Set => self.get_matching_numbers().len(),
+            MatchingMode::Multiset => self.get_matching_numbers_multiset().len()
+        }
+    }
+
+    fn find_duplicate(numbers: &[i32]) -> Option<i32> {
+        let mut seen = HashSet::new();
+        numbers.iter().copied().find(|number| !seen.insert(*number))
+    }
 }
 
-fn get_set_points(set: &HashSet<i32>) -> i32 {
-    let set_size = set.len() as u32;
-    if set_size == 0 {
-        0
-    } else {
-        2_i32.pow(set_size - 1)
+enum MatchingMode {
+    Set,
+    Multiset
+}
+
+enum CardError {
+    DuplicateWinningNumber { card_number: i32, number: i32 },
+    DuplicateOwnNumber { card_number: i32, number: i32 },
+    OutOfOrderCard { position: usize, card_number: i32 }
+}
+
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CardError::DuplicateWinningNumber { card_number, number } => write!(f, "card {} has winning number {} more than once", card_number, number),
+            CardError::DuplicateOwnNumber { card_number, number } => write!(f, "card {} has own number {} more than once", card_number, number),
+            CardError::OutOfOrderCard { position, card_number } =>
+                write!(f, "card number {} found on line {}, expected {}", card_number, position + 1, position + 1)
+        }
+    }
+}
+
+// cards are indexed by their order, so card numbers have to go 1, 2, 3... without gaps
+fn validate_cards(cards: &[Scratchcard]) -> Result<(), CardError> {
+    for (position, card) in cards.iter().enumerate() {
+        if card.card_number != position as i32 + 1 {
+            return Err(CardError::OutOfOrderCard { position, card_number: card.card_number });
+        }
+        if let Some(number) = Scratchcard::find_duplicate(&card.winning_numbers) {
+            return Err(CardError::DuplicateWinningNumber { card_number: card.card_number, number });
+        }
+        if let Some(number) = Scratchcard::find_duplicate(&card.own_numbers) {
+            return Err(CardError::DuplicateOwnNumber { card_number: card.card_number, number });
+        }
     }
+    Ok(())
+}
+
+enum PointsRule {
+    Linear,
+    Doubling,
+    Table(Vec<u64>) // points for 0, 1, 2... matches, the last entry is used for any more matches
 }
 
-fn solve_part_1(cards: &Vec<Scratchcard>) -> i32 {
-    let mut result = 0;
+impl PointsRule {
+    fn new(input: &str) -> Self {
+        match input {
+            "linear" => PointsRule::Linear,
+            "doubling" => PointsRule::Doubling,
+            _ => match input.strip_prefix("table:") {
+                Some(table) => PointsRule::Table(table.split(',').map(|points| points.parse().unwrap()).collect()),
+                None => panic!("Unknown points rule")
+            }
+        }
+    }
+
+    // None when the points don't fit in 64 bits
+    fn get_points(&self, matches: usize) -> Option<u64> {
+        match self {
+            PointsRule::Linear => Some(matches as u64),
+            PointsRule::Doubling => if matches == 0 { Some(0) } else { u32::try_from(matches - 1).ok().and_then(|power| 2_u64.checked_pow(power)) },
+            PointsRule::Table(table) => Some(*table.get(matches).or(table.last()).unwrap_or(&0))
+        }
+    }
+}
+
+struct CascadeRule {
+    offset: usize, // distance from the winning card to the first card won
+    wrap: bool // cards won past the end continue from the first card
+}
+
+impl CascadeRule {
+    fn get_prize_cards(&self, card_index: usize, matches: usize, card_count: usize) -> Vec<usize> {
+        let prize_cards = (card_index + self.offset)..(card_index + self.offset + matches);
+        if self.wrap {
+            prize_cards.map(|prize_card| prize_card % card_count).collect()
+        } else {
+            prize_cards.filter(|&prize_card| prize_card < card_count).collect()
+        }
+    }
+}
+
+fn solve_part_1(cards: &[Scratchcard], rule: &PointsRule, mode: &MatchingMode) -> Option<u64> {
+    let mut result: u64 = 0;
     for card in cards {
-        let matching_numbers = card.get_matching_numbers();
-        result += get_set_points(&matching_numbers);
+        result = result.checked_add(rule.get_points(card.get_matching_count(mode))?)?;
+    }
+    Some(result)
+}
+
+struct CardCopies {
+    copies: u64,
+    won_from: Vec<(usize, u64)> // (index of the winning card, copies won from it)
+}
+
+enum CascadeError {
+    NeverEnds { cycle: Vec<usize> }, // the cards win copies of each other, in this order
+    TooManyCopies { card_number: usize },
+    TooManyCards
+}
+
+impl fmt::Display for CascadeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CascadeError::NeverEnds { cycle } => {
+                let cards: Vec<String> = cycle.iter().chain(cycle.first()).map(|card| format!("card {}", card + 1)).collect();
+                write!(f, "the cascade never ends, copies keep being won along {}", cards.join(" -> "))
+            }
+            CascadeError::TooManyCopies { card_number } => write!(f, "card {} has more than {} copies", card_number, u64::MAX),
+            CascadeError::TooManyCards => write!(f, "there are more than {} cards in total", u64::MAX)
+        }
+    }
+}
+
+fn get_card_cascade(cards: &[Scratchcard], rule: &CascadeRule, mode: &MatchingMode) -> Result<Vec<CardCopies>, CascadeError> {
+    let prize_cards: Vec<Vec<usize>> = cards.iter().enumerate()
+        .map(|(i, card)| rule.get_prize_cards(i, card.get_matching_count(mode), cards.len()))
+        .collect();
+    // cards are scratched in topological order, so all copies of a card are known before it's scratched
+    let mut winning_cards_left = vec![0; cards.len()];
+    for &prize_card in prize_cards.iter().flatten() {
+        winning_cards_left[prize_card] += 1;
+    }
+    let mut to_scratch: BinaryHeap<Reverse<usize>> = (0..cards.len()).filter(|&i| winning_cards_left[i] == 0).map(Reverse).collect();
+    let mut cascade: Vec<CardCopies> = cards.iter().map(|_| CardCopies { copies: 1, won_from: vec![] }).collect();
+    let mut scratched = 0;
+    while let Some(Reverse(i)) = to_scratch.pop() {
+        scratched += 1;
+        let card_ammount = cascade[i].copies;
+        for &prize_card in &prize_cards[i] {
+            cascade[prize_card].copies = cascade[prize_card].copies.checked_add(card_ammount)
+                .ok_or(CascadeError::TooManyCopies { card_number: prize_card + 1 })?;
+            cascade[prize_card].won_from.push((i, card_ammount));
+            winning_cards_left[prize_card] -= 1;
+            if winning_cards_left[prize_card] == 0 {
+                to_scratch.push(Reverse(prize_card));
+            }
+        }
+    }
+    if scratched == cards.len() {
+        Ok(cascade)
+    } else {
+        Err(CascadeError::NeverEnds { cycle: find_cascade_cycle(&prize_cards, &winning_cards_left) })
     }
-    result
 }
 
-fn solve_part_2(cards: &mut Vec<Scratchcard>) -> i32 {
-    let mut result = 0;
-    let mut prize_cards = HashMap::<usize, i32>::new();
-    for (i, card) in cards.iter_mut().enumerate() {
-        let card_ammount = 1 + prize_cards.get(&i).unwrap_or(&0);
-        result += card_ammount;
-        let matching_numbers = card.get_matching_numbers();
-        for cards_won in 1..(matching_numbers.len() + 1) {
-            *prize_cards.entry(i + cards_won).or_insert(0) += card_ammount;
+// Every card which is never scratched is won by another one which isn't scratched either,
+// so going back through those winners has to run into a cycle
+fn find_cascade_cycle(prize_cards: &[Vec<usize>], winning_cards_left: &[usize]) -> Vec<usize> {
+    let unscratched = |card: usize| winning_cards_left[card] > 0;
+    let mut path = vec![(0..prize_cards.len()).find(|&card| unscratched(card)).unwrap()];
+    loop {
+        let card = *path.last().unwrap();
+        let winner = (0..prize_cards.len()).find(|&winner| unscratched(winner) && prize_cards[winner].contains(&card)).unwrap();
+        if let Some(position) = path.iter().position(|&visited| visited == winner) {
+            let mut cycle = path[position..].to_vec();
+            cycle.reverse();
+            return cycle;
         }
+        path.push(winner);
     }
+}
+
+fn cascade_to_dot(cascade: &[CardCopies]) -> String {
+    let mut result = String::from("digraph cascade {\n");
+    for (i, card) in cascade.iter().enumerate() {
+        result.push_str(&format!("    c{} [label=\"Card {}\\n{} copies\"];\n", i, i + 1, card.copies));
+    }
+    for (i, card) in cascade.iter().enumerate() {
+        for (source, copies) in &card.won_from {
+            result.push_str(&format!("    c{} -> c{} [label=\"{}\"];\n", source, i, copies));
+        }
+    }
+    result.push_str("}\n");
     result
 }
 
+fn solve_part_2(cards: &[Scratchcard], rule: &CascadeRule, mode: &MatchingMode) -> Result<u64, CascadeError> {
+    let mut result: u64 = 0;
+    for card in get_card_cascade(cards, rule, mode)? {
+        result = result.checked_add(card.copies).ok_or(CascadeError::TooManyCards)?;
+    }
+    Ok(result)
+}
+
+fn get_card_cascade_or_exit(cards: &[Scratchcard], rule: &CascadeRule, mode: &MatchingMode) -> Vec<CardCopies> {
+    match get_card_cascade(cards, rule, mode) {
+        Ok(cascade) => cascade,
+        Err(error) => {
+            eprintln!("Invalid cascade: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let task_part = args[1].parse::<i32>().unwrap();
     let file_path = &args[2];
     let file_contents = fs::read_to_string(file_path).unwrap();
     let mut cards = vec![];
     for line in file_contents.lines() {
         cards.push(Scratchcard::new(line));
     }
-    let result = match task_part {
-        1 => solve_part_1(&cards),
-        2 => solve_part_2(&mut cards),
-        _ => -1
+    if args.iter().any(|arg| arg == "--strict") {
+        if let Err(error) = validate_cards(&cards) {
+            eprintln!("Invalid scratchcards: {}", error);
+            process::exit(1);
+        }
+    }
+    let matching_mode = if args.iter().any(|arg| arg == "--multiset") { MatchingMode::Multiset } else { MatchingMode::Set };
+    let points_rule = PointsRule::new(args.iter().find_map(|arg| arg.strip_prefix("--points=")).unwrap_or("doubling"));
+    let cascade_rule = CascadeRule {
+        offset: args.iter().find_map(|arg| arg.strip_prefix("--offset=")).map_or(1, |offset| offset.parse().unwrap()),
+        wrap: args.iter().any(|arg| arg == "--wrap")
     };
-    println!("{}", result);
+    match args[1].as_str() {
+        "trace" => {
+            for (i, card) in get_card_cascade_or_exit(&cards, &cascade_rule, &matching_mode).iter().enumerate() {
+                let sources: Vec<String> = card.won_from.iter()
+                    .map(|(source, copies)| format!("{} from Card {}", copies, source + 1))
+                    .collect();
+                println!("Card {}: {} copies (1 original, {})", i + 1, card.copies, if sources.is_empty() { String::from("none won") } else { sources.join(", ") });
+            }
+        }
+        "dot" => print!("{}", cascade_to_dot(&get_card_cascade_or_exit(&cards, &cascade_rule, &matching_mode))),
+        task_part => {
+            let result = match task_part.parse::<i32>().unwrap() {
+                1 => solve_part_1(&cards, &points_rule, &matching_mode).unwrap_or_else(|| {
+                    eprintln!("Invalid points: the total is more than {}", u64::MAX);
+                    process::exit(1);
+                }),
+                2 => solve_part_2(&cards, &cascade_rule, &matching_mode).unwrap_or_else(|error| {
+                    eprintln!("Invalid cascade: {}", error);
+                    process::exit(1);
+                }),
+                _ => 0
+            };
+            println!("{}", result);
+        }
+    }
 }
@@ -1,43 +1,66 @@
+use std::cmp::min;
 use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Unbounded};
 use std::env;
+use std::fmt;
 use std::fs;
+use std::process;
 
+#[derive(Clone)]
 struct Translation {
-    destination_range_start: u32,
-    source_range_start: u32,
-    range_length: u32
+    destination_range_start: u64,
+    source_range_start: u64,
+    range_length: u64
 }
 
+#[derive(Clone)]
 struct Translator {
-    translations: BTreeMap<u32, Translation>,
-    reverse_translations: BTreeMap<u32, Translation>
+    source: String, // category name, like "seed"
+    destination: String,
+    translations: BTreeMap<u64, Translation>,
+    reverse_translations: BTreeMap<u64, Vec<Translation>> // destination ranges can overlap, so every one is kept
 }
 
 impl Translator {
-    fn new(input: &str) -> Self {
+    fn new(input: &str) -> Result<Self, ChainError> {
         let mut translations = BTreeMap::new();
         let mut reverse_translations = BTreeMap::new();
-        for line in input.lines().skip(1) {
-            let fields: Vec<&str> = line.split(' ').collect();
-            let destination_range_start = fields[0].parse::<u32>().unwrap();
-            let source_range_start = fields[1].parse::<u32>().unwrap();
-            let range_length = fields[2].parse::<u32>().unwrap();
-            translations.insert(source_range_start, Translation{destination_range_start, source_range_start, range_length});
-            reverse_translations.insert(destination_range_start, Translation{destination_range_start, source_range_start, range_length});
+        let mut lines = input.lines();
+        let malformed = |line: &str| ChainError::MalformedStage(line.to_string());
+        let header_line = lines.next().unwrap_or("");
+        let header = header_line.strip_suffix(" map:").ok_or_else(|| malformed(header_line))?;
+        let (source, destination) = header.split_once("-to-").ok_or_else(|| malformed(header_line))?;
+        for line in lines {
+            let fields: Vec<u64> = line.split(' ').map(|field| field.parse::<u64>()).collect::<Result<_, _>>().map_err(|_| malformed(line))?;
+            let &[destination_range_start, source_range_start, range_length] = fields.as_slice() else {
+                return Err(malformed(line));
+            };
+            if let Some(previous) = translations.insert(source_range_start, Translation{destination_range_start, source_range_start, range_length}) {
+                return Err(ChainError::OverlappingTranslations { category: source.to_string(), first_start: previous.source_range_start, second_start: source_range_start });
+            }
+            reverse_translations.entry(destination_range_start).or_insert_with(Vec::new).push(Translation{destination_range_start, source_range_start, range_length});
         }
-        Self {
+        let sorted_translations: Vec<&Translation> = translations.values().collect();
+        for pair in sorted_translations.windows(2) {
+            if pair[0].source_range_start + pair[0].range_length > pair[1].source_range_start {
+                return Err(ChainError::OverlappingTranslations { category: source.to_string(), first_start: pair[0].source_range_start, second_start: pair[1].source_range_start });
+            }
+        }
+        Ok(Self {
+            source: source.to_string(),
+            destination: destination.to_string(),
             translations,
             reverse_translations
-        }
+        })
     }
 
-    fn translate(&self, source: u32) -> u32 {
+    fn translate(&self, source: u64) -> u64 {
         let mut range = self.translations.range(..=source);
         match range.next_back() {
             None => source,
             Some((_, translator)) => {
                 let start_distance = source - translator.source_range_start;
-                if start_distance <= translator.range_length {
+                if start_distance < translator.range_length {
                     translator.destination_range_start + start_distance
                 }
                 else {
@@ -47,19 +70,163 @@ impl Translator {
         }
     }
 
-    fn reverse_translate(&self, destination: u32) -> u32 {
-        let mut range = self.reverse_translations.range(..=destination);
-        match range.next_back() {
-            None => destination,
-            Some((_, translator)) => {
-                let start_distance = destination - translator.destination_range_start;
-                if start_distance <= translator.range_length {
-                    translator.source_range_start + start_distance
-                }
-                else {
-                    destination
+    // ranges are (start, length) pairs, they're split wherever a translation starts or ends
+    fn translate_ranges(&self, ranges: &[(u64, u64)]) -> Vec<(u64, u64)> {
+        let mut result = vec![];
+        for &(range_start, range_length) in ranges {
+            let mut start = range_start;
+            let mut length = range_length;
+            while length > 0 {
+                let covering = self.translations.range(..=start).next_back()
+                    .map(|(_, translator)| translator)
+                    .filter(|translator| start - translator.source_range_start < translator.range_length);
+                let chunk_length = match covering {
+                    Some(translator) => {
+                        let start_distance = start - translator.source_range_start;
+                        let chunk_length = min(length, translator.range_length - start_distance);
+                        result.push((translator.destination_range_start + start_distance, chunk_length));
+                        chunk_length
+                    }
+                    None => {
+                        // untranslated values map to themselves until the next translation starts,
+                        // one starting right here has to be empty, otherwise it would be covering
+                        let chunk_length = match self.translations.range((Excluded(start), Unbounded)).next() {
+                            Some((next_start, _)) => min(length, next_start - start),
+                            None => length
+                        };
+                        result.push((start, chunk_length));
+                        chunk_length
+                    }
+                };
+                length -= chunk_length;
+                start = start.wrapping_add(chunk_length);
+            }
+        }
+        result
+    }
+
+    fn to_piecewise_map(&self) -> PiecewiseMap {
+        // empty translations don't move anything, but would hide the identity after them
+        let translations: Vec<&Translation> = self.translations.values().filter(|translation| translation.range_length > 0).collect();
+        let mut segments = BTreeMap::from([(0, 0)]);
+        for translation in &translations {
+            segments.insert(translation.source_range_start as u128 + translation.range_length as u128, 0);
+        }
+        // a translation starting where another one ends takes over from the identity
+        for translation in &translations {
+            segments.insert(translation.source_range_start as u128, translation.destination_range_start as i128 - translation.source_range_start as i128);
+        }
+        PiecewiseMap::new(segments.into_iter().filter(|(start, _)| *start < DOMAIN_END).map(|(start, offset)| (start as u64, offset)).collect())
+    }
+
+    // every source translated to the destination, which is its own source unless a translation moves it away
+    fn reverse_translate(&self, destination: u64) -> Vec<u64> {
+        let mut sources: Vec<u64> = self.reverse_translations.range(..=destination)
+            .flat_map(|(_, translations)| translations)
+            .filter(|translation| destination - translation.destination_range_start < translation.range_length)
+            .map(|translation| translation.source_range_start + destination - translation.destination_range_start)
+            .collect();
+        let translated_away = self.translations.range(..=destination).next_back()
+            .is_some_and(|(_, translation)| destination - translation.source_range_start < translation.range_length);
+        if !translated_away {
+            sources.push(destination);
+        }
+        sources.sort();
+        sources
+    }
+}
+
+const DOMAIN_END: u128 = 1 << 64;
+
+// Covers the whole domain, every value in a segment is moved by the segment's offset
+struct PiecewiseMap {
+    segments: Vec<(u64, i128)> // (source start, offset), each segment ends where the next one starts
+}
+
+impl PiecewiseMap {
+    fn new(segments: Vec<(u64, i128)>) -> Self {
+        // neighbouring segments with the same offset are merged
+        let mut merged: Vec<(u64, i128)> = vec![];
+        for (start, offset) in segments {
+            if merged.last().is_none_or(|(_, last_offset)| *last_offset != offset) {
+                merged.push((start, offset));
+            }
+        }
+        Self {segments: merged}
+    }
+
+    fn segment_end(&self, index: usize) -> u128 {
+        self.segments.get(index + 1).map_or(DOMAIN_END, |(start, _)| *start as u128)
+    }
+
+    fn get_offset(&self, source: u64) -> i128 {
+        let index = self.segments.partition_point(|(start, _)| *start <= source) - 1;
+        self.segments[index].1
+    }
+
+    fn translate(&self, source: u64) -> u64 {
+        (source as i128 + self.get_offset(source)) as u64
+    }
+
+    // the map doesn't have to be injective, so there can be any number of sources
+    fn reverse_translate(&self, destination: u64) -> Vec<u64> {
+        let mut sources = vec![];
+        for (i, (start, offset)) in self.segments.iter().enumerate() {
+            let source = destination as i128 - offset;
+            if source >= *start as i128 && source < self.segment_end(i) as i128 {
+                sources.push(source as u64);
+            }
+        }
+        sources
+    }
+
+    // the result first applies self and then next
+    fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let mut segments = vec![];
+        for (i, (start, offset)) in self.segments.iter().enumerate() {
+            let end = self.segment_end(i);
+            let mut breakpoints = vec![*start];
+            for (next_start, _) in &next.segments {
+                let source = *next_start as i128 - offset;
+                if source > *start as i128 && source < end as i128 {
+                    breakpoints.push(source as u64);
                 }
             }
+            for breakpoint in breakpoints {
+                let destination = (breakpoint as i128 + offset) as u64;
+                segments.push((breakpoint, offset + next.get_offset(destination)));
+            }
+        }
+        PiecewiseMap::new(segments)
+    }
+}
+
+impl fmt::Display for PiecewiseMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (start, offset)) in self.segments.iter().enumerate() {
+            let end = self.segment_end(i);
+            writeln!(f, "[{}, {}) -> [{}, {}) offset {:+}", start, end, *start as i128 + offset, end as i128 + offset, offset)?;
+        }
+        Ok(())
+    }
+}
+
+enum ChainError {
+    MismatchedStages { stage: usize, destination: String, next_source: String },
+    UnknownCategory(String),
+    MalformedStage(String),
+    OverlappingTranslations { category: String, first_start: u64, second_start: u64 }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainError::MismatchedStages { stage, destination, next_source } =>
+                write!(f, "stage {} translates to {} but the next stage translates from {}", stage + 1, destination, next_source),
+            ChainError::UnknownCategory(category) => write!(f, "unknown category {}", category),
+            ChainError::MalformedStage(line) => write!(f, "can't parse the line {:?}", line),
+            ChainError::OverlappingTranslations { category, first_start, second_start } =>
+                write!(f, "{} translations starting at {} and {} overlap", category, first_start, second_start)
         }
     }
 }
@@ -69,15 +236,62 @@ struct TranslatorChain {
 }
 
 impl TranslatorChain {
-    fn new(input: &str) -> Self {
+    fn new(input: &str) -> Result<Self, ChainError> {
         let mut translators = vec![];
-        for translator_input in input.split("\n\n") {
-            translators.push(Translator::new(translator_input));
+        // extra blank lines, like at the end of the file, don't make empty stages
+        for translator_input in input.split("\n\n").map(str::trim).filter(|translator_input| !translator_input.is_empty()) {
+            translators.push(Translator::new(translator_input)?);
+        }
+        Ok(Self {translators})
+    }
+
+    fn validate(&self) -> Result<(), ChainError> {
+        for (stage, pair) in self.translators.windows(2).enumerate() {
+            if pair[0].destination != pair[1].source {
+                return Err(ChainError::MismatchedStages {
+                    stage,
+                    destination: pair[0].destination.clone(),
+                    next_source: pair[1].source.clone()
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn get_categories(&self) -> Vec<&str> {
+        let mut categories: Vec<&str> = self.translators.iter().map(|translator| translator.source.as_str()).collect();
+        if let Some(last) = self.translators.last() {
+            categories.push(&last.destination);
+        }
+        categories
+    }
+
+    // the stages needed to get from one category to another, the chain has to be valid
+    fn get_sub_chain(&self, from: &str, to: &str) -> Result<TranslatorChain, ChainError> {
+        let (from_index, to_index) = self.get_category_indices(from, to)?;
+        Ok(TranslatorChain {translators: self.translators[from_index.min(to_index)..from_index.max(to_index)].to_vec()})
+    }
+
+    fn get_category_indices(&self, from: &str, to: &str) -> Result<(usize, usize), ChainError> {
+        let categories = self.get_categories();
+        let find = |category: &str| categories.iter().position(|c| *c == category).ok_or(ChainError::UnknownCategory(category.to_string()));
+        Ok((find(from)?, find(to)?))
+    }
+
+    // every value of the "to" category matching the given one, going backwards there can be any number of them
+    fn query(&self, from: &str, to: &str, value: u64) -> Result<Vec<u64>, ChainError> {
+        let (from_index, to_index) = self.get_category_indices(from, to)?;
+        let sub_chain = self.get_sub_chain(from, to)?;
+        if from_index <= to_index {
+            Ok(vec![sub_chain.translate(value)])
+        } else {
+            let mut sources = sub_chain.reverse_translate(value);
+            sources.sort();
+            Ok(sources)
         }
-        Self {translators}
     }
 
-    fn translate(&self, source: u32) -> u32 {
+    fn translate(&self, source: u64) -> u64 {
         let mut destination  = source;
         for translator in &self.translators {
             destination = translator.translate(destination);
@@ -85,17 +299,33 @@ impl TranslatorChain {
         destination
     }
 
-    fn reverse_translate(&self, destination: u32) -> u32 {
-        let mut source = destination;
+    fn translate_ranges(&self, ranges: &[(u64, u64)]) -> Vec<(u64, u64)> {
+        let mut destinations = ranges.to_vec();
+        for translator in &self.translators {
+            destinations = translator.translate_ranges(&destinations);
+        }
+        destinations
+    }
+
+    fn compose(&self) -> PiecewiseMap {
+        let mut composed = PiecewiseMap::new(vec![(0, 0)]);
+        for translator in &self.translators {
+            composed = composed.then(&translator.to_piecewise_map());
+        }
+        composed
+    }
+
+    fn reverse_translate(&self, destination: u64) -> Vec<u64> {
+        let mut sources = vec![destination];
         for translator in self.translators.iter().rev() {
-            source = translator.reverse_translate(source);
+            sources = sources.iter().flat_map(|source| translator.reverse_translate(*source)).collect();
         }
-        source
+        sources
     }
 }
 
 struct SeedRanges {
-    ranges: BTreeMap<u32, u32>
+    ranges: BTreeMap<u64, u64>
 }
 
 impl SeedRanges {
@@ -104,73 +334,235 @@ impl SeedRanges {
             ranges: BTreeMap::new()
         }
     }
-
-    fn is_in_ranges(&self, seed: u32) -> bool {
-        let mut range = self.ranges.range(..=seed);
-        match range.next_back() {
-            None => false,
-            Some((range_start, range_length)) => {
-                let start_distance = seed - range_start;
-                if start_distance <= *range_length {
-                    true
-                }
-                else {
-                    false
-                }
-            }
-        }
-    }
 }
 
 
 
-fn get_seeds(input: &str) -> Vec<u32> {
+fn get_seeds(input: &str) -> Vec<u64> {
     let (_, seed_list) = input.split_once(": ").unwrap();
     let mut seeds = vec![];
     for seed in seed_list.split(' ') {
-        seeds.push(seed.parse::<u32>().unwrap())
+        seeds.push(seed.parse::<u64>().unwrap())
     }
     seeds
 }
 
-fn parse_input(input: &str) -> (Vec<u32>, TranslatorChain) {
+fn parse_input(input: &str) -> Result<(Vec<u64>, TranslatorChain), ChainError> {
     let (seeds_input, translation_input) = input.split_once("\n\n").unwrap();
-    (get_seeds(seeds_input), TranslatorChain::new(translation_input))
+    let chain = TranslatorChain::new(translation_input)?;
+    chain.validate()?;
+    Ok((get_seeds(seeds_input), chain))
 }
 
-fn solve_part_1(seeds: &Vec<u32>, chain: &TranslatorChain) -> i32 {
+fn solve_part_1(seeds: &Vec<u64>, chain: &TranslatorChain) -> u64 {
     let mut destinations = vec![];
     for seed in seeds {
         destinations.push(chain.translate(*seed));
     }
-    *destinations.iter().min().unwrap() as i32
+    *destinations.iter().min().unwrap()
 }
 
-fn solve_part_2(seeds: &Vec<u32>, chain: &TranslatorChain) -> i32 {
+fn get_seed_ranges(seeds: &[u64]) -> SeedRanges {
     let mut seed_ranges = SeedRanges::new();
     for chunk in seeds.chunks(2) {
         seed_ranges.ranges.insert(chunk[0], chunk[1]);
     }
-    // This is a brute force solution, it would be better to combine all translations into one
-    for destination in 0.. {
-        let source = chain.reverse_translate(destination);
-        if seed_ranges.is_in_ranges(source) {
-            return destination as i32;
-        }
-    }
-    -1
+    seed_ranges
+}
+
+fn solve_part_2(seeds: &[u64], chain: &TranslatorChain) -> u64 {
+    let seed_ranges: Vec<(u64, u64)> = get_seed_ranges(seeds).ranges.into_iter().collect();
+    let destination_ranges = chain.translate_ranges(&seed_ranges);
+    destination_ranges.iter().map(|(start, _)| *start).min().unwrap()
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let task_part = args[1].parse::<u32>().unwrap();
     let file_path = &args[2];
     let file_contents = fs::read_to_string(file_path).unwrap();
-    let (seeds, chain) = parse_input(&file_contents);
-    let result = match task_part {
-        1 => solve_part_1(&seeds, &chain),
-        2 => solve_part_2(&seeds, &chain),
-        _ => -1
+    let (seeds, chain) = match parse_input(&file_contents) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("Invalid almanac: {}", error);
+            process::exit(1);
+        }
+    };
+    match args[1].as_str() {
+        "compose" => {
+            print!("{}", chain.compose());
+            return;
+        }
+        "forward" => {
+            let source = args[3].parse::<u64>().unwrap();
+            println!("{}", chain.compose().translate(source));
+            return;
+        }
+        "inverse" => {
+            let destination = args[3].parse::<u64>().unwrap();
+            println!("{:?}", chain.compose().reverse_translate(destination));
+            return;
+        }
+        "query" => {
+            // usage: query <file> <from category> <to category> <value>, works both ways along the chain
+            match chain.query(&args[3], &args[4], args[5].parse::<u64>().unwrap()) {
+                Ok(values) => println!("{:?}", values),
+                Err(error) => {
+                    eprintln!("Invalid query: {}", error);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+    let result = match args[1].as_str() {
+        "1" => solve_part_1(&seeds, &chain),
+        "2" => solve_part_2(&seeds, &chain),
+        _ => 0
     };
     println!("{}", result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    impl SeedRanges {
+        fn is_in_ranges(&self, seed: u64) -> bool {
+            let mut range = self.ranges.range(..=seed);
+            match range.next_back() {
+                None => false,
+                Some((range_start, range_length)) => {
+                    let start_distance = seed - range_start;
+                    start_distance < *range_length
+                }
+            }
+        }
+    }
+
+    // The search solve_part_2 replaced, trying every location from 0 on
+    fn solve_part_2_brute_force(seeds: &[u64], chain: &TranslatorChain) -> u64 {
+        let seed_ranges = get_seed_ranges(seeds);
+        for destination in 0.. {
+            if chain.reverse_translate(destination).iter().any(|source| seed_ranges.is_in_ranges(*source)) {
+                return destination;
+            }
+        }
+        0
+    }
+
+    // The straightforward definition of a translation, used to check the faster structures
+    fn naive_translate(translator: &Translator, source: u64) -> u64 {
+        for translation in translator.translations.values() {
+            if source >= translation.source_range_start && source - translation.source_range_start < translation.range_length {
+                return translation.destination_range_start + source - translation.source_range_start;
+            }
+        }
+        source
+    }
+
+    fn naive_chain_translate(chain: &TranslatorChain, source: u64) -> u64 {
+        chain.translators.iter().fold(source, |value, translator| naive_translate(translator, value))
+    }
+
+    // Every reverse translation has to translate back, and has to contain each checked value translating to it
+    fn check_reverse(name: &str, values: &BTreeSet<u64>, sources: &[u64], destination: u64, forward: impl Fn(u64) -> u64) -> Vec<String> {
+        let mut mismatches = vec![];
+        for &source in sources {
+            if forward(source) != destination {
+                mismatches.push(format!("{} reverse translates {} to {}, which doesn't translate back", name, destination, source));
+            }
+        }
+        for &value in values {
+            if forward(value) == destination && !sources.contains(&value) {
+                mismatches.push(format!("{} reverse translates {} to {:?}, missing {}", name, destination, sources, value));
+            }
+        }
+        mismatches
+    }
+
+    // Compares everything against the naive definitions, on every value next to a range boundary and on all small values
+    fn check_against_naive(input: &str) -> Vec<String> {
+        let (seeds, chain) = parse_input(input).ok().unwrap();
+        let seed_ranges = get_seed_ranges(&seeds);
+        let mut boundaries: Vec<u64> = seed_ranges.ranges.iter().flat_map(|(start, length)| [*start, start + length]).collect();
+        for translator in &chain.translators {
+            for translation in translator.translations.values() {
+                boundaries.extend([translation.source_range_start, translation.source_range_start + translation.range_length]);
+                boundaries.extend([translation.destination_range_start, translation.destination_range_start + translation.range_length]);
+            }
+        }
+        let mut values: BTreeSet<u64> = (0..200).collect();
+        for boundary in boundaries {
+            values.extend([boundary.saturating_sub(1), boundary, boundary.saturating_add(1)]);
+        }
+        let mut mismatches = vec![];
+        let composed = chain.compose();
+        let destination_ranges = chain.translate_ranges(&seed_ranges.ranges.iter().map(|(start, length)| (*start, *length)).collect::<Vec<_>>());
+        for &value in &values {
+            for translator in &chain.translators {
+                if translator.translate(value) != naive_translate(translator, value) {
+                    mismatches.push(format!("{}-to-{} translates {} to {}, expected {}", translator.source, translator.destination, value, translator.translate(value), naive_translate(translator, value)));
+                }
+                let name = format!("{}-to-{}", translator.source, translator.destination);
+                mismatches.extend(check_reverse(&name, &values, &translator.reverse_translate(value), value, |source| naive_translate(translator, source)));
+            }
+            let expected = naive_chain_translate(&chain, value);
+            if chain.translate(value) != expected {
+                mismatches.push(format!("chain translates {} to {}, expected {}", value, chain.translate(value), expected));
+            }
+            if composed.translate(value) != expected {
+                mismatches.push(format!("composed map translates {} to {}, expected {}", value, composed.translate(value), expected));
+            }
+            let forward = |source| naive_chain_translate(&chain, source);
+            mismatches.extend(check_reverse("chain", &values, &chain.reverse_translate(value), value, forward));
+            mismatches.extend(check_reverse("composed map", &values, &composed.reverse_translate(value), value, forward));
+            let in_ranges = seed_ranges.ranges.iter().any(|(start, length)| value >= *start && value - start < *length);
+            if seed_ranges.is_in_ranges(value) != in_ranges {
+                mismatches.push(format!("seed ranges contain {}: {}, expected {}", value, seed_ranges.is_in_ranges(value), in_ranges));
+            }
+            if in_ranges && !destination_ranges.iter().any(|(start, length)| expected >= *start && expected - start < *length) {
+                mismatches.push(format!("seed {} translates to {}, which is missing from the translated ranges", value, expected));
+            }
+        }
+        mismatches
+    }
+
+    fn assert_matches_naive(input: &str, lowest_location: u64) {
+        assert_eq!(check_against_naive(input), Vec::<String>::new());
+        let (seeds, chain) = parse_input(input).ok().unwrap();
+        assert_eq!(solve_part_2(&seeds, &chain), lowest_location);
+        assert_eq!(solve_part_2_brute_force(&seeds, &chain), lowest_location);
+    }
+
+    #[test]
+    fn example_matches_naive() {
+        assert_matches_naive(include_str!("../example.txt"), 46);
+    }
+
+    #[test]
+    fn shared_destination_matches_naive() {
+        assert_matches_naive("seeds: 0 3\n\nseed-to-soil map:\n10 0 5\n10 20 5", 10);
+    }
+
+    #[test]
+    fn overlapping_destinations_match_naive() {
+        assert_matches_naive("seeds: 30 5\n\nseed-to-soil map:\n0 10 8\n4 30 6\n\nsoil-to-location map:\n100 0 3\n3 100 3", 4);
+    }
+
+    #[test]
+    fn empty_translation_matches_naive() {
+        assert_matches_naive("seeds: 40 10\n\nseed-to-soil map:\n10 50 0\n0 45 3", 0);
+    }
+
+    #[test]
+    fn trailing_blank_lines_are_ignored() {
+        assert_matches_naive("seeds: 79 14\n\nseed-to-soil map:\n50 98 2\n\n", 79);
+    }
+
+    #[test]
+    fn overlapping_sources_are_rejected() {
+        assert!(parse_input("seeds: 0 1\n\nseed-to-soil map:\n10 0 5\n20 4 5").is_err());
+    }
+}
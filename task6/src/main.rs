@@ -1,7 +1,9 @@
+use std::cmp::{max, min};
 use std::env;
 use std::fs;
+use std::process;
 
-fn get_times_distances(input: &str) -> (Vec<f64>, Vec<f64>) {
+fn get_times_distances(input: &str) -> (Vec<u64>, Vec<u64>) {
     let (time_line, distance_line) = input.split_once('\n').unwrap();
     let mut times = vec![];
     let (_, time_line) = time_line.split_once(':').unwrap();
@@ -22,47 +24,140 @@ fn get_times_distances(input: &str) -> (Vec<f64>, Vec<f64>) {
     (times, distances)
 }
 
-fn solve_quadratic_equation(b: f64, c: f64) -> (f64, f64) {
-    let square_root_of_delta = (b.powf(2.) - 4. * c).sqrt();
-    ((-b - square_root_of_delta) / 2., (-b + square_root_of_delta) / 2.)
+// inclusive range of button hold times that beat the record, found without floating point
+fn get_winning_hold_times(time: u64, distance: u64) -> Option<(u64, u64)> {
+    let time = time as u128;
+    let distance = distance as u128;
+    let beats_record = |hold: u128| hold * (time - hold) > distance;
+    // roots of hold * (time - hold) = distance are (time -+ sqrt(time^2 - 4 * distance)) / 2
+    let delta = (time * time).checked_sub(4 * distance)?;
+    let mut first = (time - delta.isqrt()) / 2;
+    // the integer square root is only an estimate of the root, so the boundary is checked exactly
+    while first > 0 && beats_record(first - 1) {
+        first -= 1;
+    }
+    while first <= time / 2 && !beats_record(first) {
+        first += 1;
+    }
+    if first > time / 2 {
+        return None;
+    }
+    // the distance is symmetric around time / 2
+    Some((first as u64, (time - first) as u64))
+}
+
+struct RaceModel {
+    acceleration: u64, // speed gained for every ms the button is held
+    max_speed: Option<u64>,
+    max_hold: Option<u64>,
+    min_margin: u64 // how much further than the record the boat has to go, 1 means just beating it
+}
+
+impl RaceModel {
+    fn new(args: &[String]) -> Self {
+        let get_option = |name: &str| args.iter().find_map(|arg| arg.strip_prefix(name)).map(|value| value.parse::<u64>().unwrap());
+        Self {
+            acceleration: get_option("--acceleration=").unwrap_or(1),
+            max_speed: get_option("--max-speed="),
+            max_hold: get_option("--max-hold="),
+            min_margin: get_option("--margin=").unwrap_or(1)
+        }
+    }
+
+    fn get_winning_hold_times(&self, time: u64, record: u64) -> Option<(u64, u64)> {
+        let last_allowed = min(time, self.max_hold.unwrap_or(time));
+        let target = record + self.min_margin;
+        if target == 0 {
+            return Some((0, last_allowed));
+        }
+        // from now on the boat has to go further than threshold
+        let threshold = target - 1;
+        if self.acceleration == 0 {
+            return None;
+        }
+        // while the speed is below max_speed, acceleration * hold * (time - hold) > threshold
+        // is the same as hold * (time - hold) > threshold / acceleration
+        let mut winning = get_winning_hold_times(time, threshold / self.acceleration);
+        if let Some(max_speed) = self.max_speed {
+            let first_saturated = max_speed.div_ceil(self.acceleration);
+            winning = winning.and_then(|winning| first_saturated.checked_sub(1).and_then(|last| intersect(winning, (0, last))));
+            // at max speed, max_speed * (time - hold) > threshold, so time - hold > threshold / max_speed
+            let saturated_winning = threshold.checked_div(max_speed)
+                .and_then(|min_remaining| time.checked_sub(min_remaining + 1))
+                .and_then(|last| intersect((first_saturated, time), (0, last)));
+            // the distance is concave in the hold time, so both parts together form one interval
+            winning = match (winning, saturated_winning) {
+                (Some((first, _)), Some((_, last))) => Some((first, last)),
+                (winning, saturated_winning) => winning.or(saturated_winning)
+            };
+        }
+        winning.and_then(|winning| intersect(winning, (0, last_allowed)))
+    }
+
+    fn count_winning_hold_times(&self, time: u64, record: u64) -> u64 {
+        match self.get_winning_hold_times(time, record) {
+            Some((first, last)) => last - first + 1,
+            None => 0
+        }
+    }
+}
+
+fn intersect(a: (u64, u64), b: (u64, u64)) -> Option<(u64, u64)> {
+    let first = max(a.0, b.0);
+    let last = min(a.1, b.1);
+    if first <= last {
+        Some((first, last))
+    } else {
+        None
+    }
 }
 
-fn solve_part_1(times: &Vec<f64>, distances: &Vec<f64>) -> f64 {
-    let mut result = 1.;
+fn solve_part_1(times: &[u64], distances: &[u64], model: &RaceModel) -> Result<u128, String> {
+    let mut result: u128 = 1;
     for (time, distance) in times.iter().zip(distances.iter()) {
-        let (x1, x2) = solve_quadratic_equation(-time, *distance);
-        let x1 = if x1.fract() == 0. {x1 + 1.} else {x1};
-        let x2 = if x2.fract() == 0. {x2 - 1.} else {x2};
-        result *= x2.floor() - x1.ceil() + 1.
+        result = result.checked_mul(model.count_winning_hold_times(*time, *distance) as u128)
+            .ok_or(format!("the product of the ways to win is more than {}", u128::MAX))?;
     }
-    result
+    Ok(result)
 }
 
-fn solve_part_2(times: &Vec<f64>, distances: &Vec<f64>) -> f64 {
+fn solve_part_2(times: &[u64], distances: &[u64], model: &RaceModel) -> Result<u128, String> {
     let mut time_string = String::new();
     let mut distance_string = String::new();
     for (time, distance) in times.iter().zip(distances.iter()) {
         time_string.push_str(&time.to_string());
         distance_string.push_str(&distance.to_string());
     }
-    let time = time_string.parse::<f64>().unwrap();
-    let distance = distance_string.parse::<f64>().unwrap();
-    let (x1, x2) = solve_quadratic_equation(-time, distance);
-    let x1 = if x1.fract() == 0. {x1 + 1.} else {x1};
-    let x2 = if x2.fract() == 0. {x2 - 1.} else {x2};
-    x2.floor() - x1.ceil() + 1.
+    let time = time_string.parse::<u64>().map_err(|_| format!("the concatenated time {} is more than {}", time_string, u64::MAX))?;
+    let distance = distance_string.parse::<u64>().map_err(|_| format!("the concatenated distance {} is more than {}", distance_string, u64::MAX))?;
+    Ok(model.count_winning_hold_times(time, distance) as u128)
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let task_part = args[1].parse::<i32>().unwrap();
     let file_path = &args[2];
     let file_contents = fs::read_to_string(file_path).unwrap();
     let (times, distances) = get_times_distances(&file_contents);
-    let result = match task_part {
-        1 => solve_part_1(&times, &distances),
-        2 => solve_part_2(&times, &distances),
-        _ => -1.
+    let model = RaceModel::new(&args);
+    if args[1] == "intervals" {
+        for (time, distance) in times.iter().zip(distances.iter()) {
+            match model.get_winning_hold_times(*time, *distance) {
+                Some((first, last)) => println!("time {} record {}: hold {} to {} ms ({} ways)", time, distance, first, last, last - first + 1),
+                None => println!("time {} record {}: can't win", time, distance)
+            }
+        }
+        return;
+    }
+    let result = match args[1].parse::<i32>().unwrap() {
+        1 => solve_part_1(&times, &distances, &model),
+        2 => solve_part_2(&times, &distances, &model),
+        _ => Ok(0)
     };
-    println!("{}", result);
+    match result {
+        Ok(result) => println!("{}", result),
+        Err(error) => {
+            eprintln!("Can't count the ways to win: {}", error);
+            process::exit(1);
+        }
+    }
 }
@@ -1,180 +1,278 @@
-use core::cmp::Ordering;
-use std::collections::HashSet;
+use core::cmp::{min, Ordering, Reverse};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 
 #[derive(PartialEq, Eq)]
-enum JMeaning {
-    Jack,
-    Joker
+enum WildcardScope {
+    HandType, // wild cards only change the hand type, they're compared by their own value
+    HandTypeAndCards // wild cards are also compared as the card they stand for
 }
 
-static mut j_meaning: JMeaning = JMeaning::Jack;
+type SubstitutionKey = (usize, Vec<u32>); // (hand type rank, card values)
 
-#[derive(PartialOrd, Ord, PartialEq, Eq, Debug)]
-enum HandType {
-    FiveOfAKind,
-    FourOfAKind,
-    FullHouse,
-    ThreeOfAKind,
-    TwoPairs,
-    Pair,
-    HighCard
+struct CardRules {
+    card_order: Vec<char>, // from the weakest to the strongest card
+    wild_cards: Vec<char>,
+    wildcard_scope: WildcardScope,
+    hand_size: Option<usize>, // any size is accepted when not given
+    type_ranking: Option<Vec<HandType>> // from the weakest to the strongest, when not given more of the same card is stronger
 }
 
-impl HandType {
-    fn get(cards: &Vec<char>) -> Self {
-        let mut checked_values = HashSet::new();
-        let mut pairs = 0;
-        let mut three_of_a_kinds = 0;
-        for card in cards {
-            if checked_values.contains(card) {
-                continue;
+impl CardRules {
+    fn jacks() -> Self {
+        Self {
+            card_order: "23456789TJQKA".chars().collect(),
+            wild_cards: vec![],
+            wildcard_scope: WildcardScope::HandType,
+            hand_size: Some(5),
+            type_ranking: None
+        }
+    }
+
+    fn jokers() -> Self {
+        Self {
+            card_order: "J23456789TQKA".chars().collect(),
+            wild_cards: vec!['J'],
+            wildcard_scope: WildcardScope::HandType,
+            hand_size: Some(5),
+            type_ranking: None
+        }
+    }
+
+    // --order=<cards from weakest> --wild=<cards> --scope=type|cards --hand-size=<n|any>
+    // --ranking=<signatures from weakest, like 11111,2111,221,311,32,41,5>, anything not given is taken from base
+    fn from_args(args: &[String], base: Self) -> Self {
+        let get_option = |name: &str| args.iter().find_map(|arg| arg.strip_prefix(name));
+        Self {
+            card_order: get_option("--order=").map_or(base.card_order, |order| order.chars().collect()),
+            wild_cards: get_option("--wild=").map_or(base.wild_cards, |wild_cards| wild_cards.chars().collect()),
+            wildcard_scope: match get_option("--scope=") {
+                Some("type") => WildcardScope::HandType,
+                Some("cards") => WildcardScope::HandTypeAndCards,
+                Some(_) => panic!("Unknown wildcard scope"),
+                None => base.wildcard_scope
+            },
+            hand_size: match get_option("--hand-size=") {
+                Some("any") => None,
+                Some(hand_size) => Some(hand_size.parse().unwrap()),
+                None => base.hand_size
+            },
+            type_ranking: get_option("--ranking=").map_or(base.type_ranking, |ranking| Some(ranking.split(',').map(HandType::parse).collect()))
+        }
+    }
+
+    fn card_to_value(&self, card: char) -> u32 {
+        match self.card_order.iter().position(|&c| c == card) {
+            Some(position) => position as u32,
+            None => panic!("Unknown card {}", card)
+        }
+    }
+
+    fn is_wild(&self, card: char) -> bool {
+        self.wild_cards.contains(&card)
+    }
+
+    // None when the hand type is missing from a custom ranking
+    fn get_type_rank(&self, hand_type: &HandType) -> Option<usize> {
+        match &self.type_ranking {
+            Some(ranking) => ranking.iter().position(|ranked| ranked == hand_type),
+            None => {
+                // every possible signature of the same size, ordered so that bigger groups come first
+                let mut signatures = HandType::get_all_signatures(hand_type.signature.iter().sum(), usize::MAX);
+                signatures.sort();
+                signatures.iter().position(|signature| *signature == hand_type.signature)
             }
-            checked_values.insert(card);
-            match cards.iter().filter(|&n| *n == *card).count() {
-                2 => pairs += 1,
-                3 => three_of_a_kinds +=1,
-                4 => return HandType::FourOfAKind,
-                5 => return HandType::FiveOfAKind,
-                6 => return HandType::FiveOfAKind,
-                _ => continue
+        }
+    }
+
+    // Tries every way of adding the wild cards to the groups of equal cards, or of making new groups
+    // with them, and returns the cards the wild cards should stand for, in order of appearance
+    fn get_best_substitution(&self, cards: &[char]) -> Vec<char> {
+        let wild_positions: Vec<usize> = (0..cards.len()).filter(|&i| self.is_wild(cards[i])).collect();
+        if wild_positions.is_empty() {
+            return cards.to_vec();
+        }
+        let mut groups: Vec<char> = vec![];
+        for &card in cards {
+            if !self.is_wild(card) && !groups.contains(&card) {
+                groups.push(card);
+            }
+        }
+        // a new group is best made of the strongest cards not in the hand yet
+        let new_groups: Vec<char> = self.card_order.iter().rev().copied()
+            .filter(|card| !self.is_wild(*card) && !groups.contains(card))
+            .take(wild_positions.len())
+            .collect();
+        let candidates = [groups, new_groups].concat();
+        let mut best = None;
+        self.try_substitutions(cards, &wild_positions, &candidates, &mut vec![], &mut best);
+        match best {
+            Some((_, substituted_cards)) => substituted_cards,
+            None => cards.to_vec() // there are no other cards to stand for, or no ranked substitution
+        }
+    }
+
+    fn try_substitutions(&self, cards: &[char], wild_positions: &[usize], candidates: &[char],
+                         substitutes: &mut Vec<char>, best: &mut Option<(SubstitutionKey, Vec<char>)>) {
+        if substitutes.len() == wild_positions.len() {
+            // wild cards are compared in order, so the strongest substitutes go first
+            let mut ordered_substitutes = substitutes.clone();
+            ordered_substitutes.sort_by_key(|&card| Reverse(self.card_to_value(card)));
+            let mut substituted_cards = cards.to_vec();
+            for (&position, &substitute) in wild_positions.iter().zip(ordered_substitutes.iter()) {
+                substituted_cards[position] = substitute;
+            }
+            // substitutions giving a type missing from the ranking can't be used
+            let Some(type_rank) = self.get_type_rank(&HandType::new(&substituted_cards)) else {
+                return;
             };
+            let key = (type_rank, self.get_card_values(cards, &substituted_cards));
+            if best.as_ref().is_none_or(|(best_key, _)| key > *best_key) {
+                *best = Some((key, substituted_cards));
+            }
+            return;
         }
-        if three_of_a_kinds == 1 && pairs == 1 {
-            HandType::FullHouse
-        } else if three_of_a_kinds == 1 {
-            HandType::ThreeOfAKind
-        } else if pairs == 2 {
-            HandType::TwoPairs
-        } else if pairs == 1 {
-            HandType::Pair
-        } else {
-            HandType:: HighCard
+        // wild cards are interchangeable, so only combinations are tried, not every order
+        for (i, &candidate) in candidates.iter().enumerate() {
+            substitutes.push(candidate);
+            self.try_substitutions(cards, wild_positions, &candidates[i..], substitutes, best);
+            substitutes.pop();
         }
     }
 
-    fn repetitions_to_type(repetitions: u32) -> Self {
-        match repetitions {
-            1 => HandType::HighCard,
-            2 => HandType::Pair,
-            3 => HandType::ThreeOfAKind,
-            4 => HandType::FourOfAKind,
-            5 => HandType::FiveOfAKind,
-            6 => HandType::FiveOfAKind,
-            _ => HandType::HighCard
+    fn get_card_values(&self, cards: &[char], substituted_cards: &[char]) -> Vec<u32> {
+        match self.wildcard_scope {
+            WildcardScope::HandType => cards.iter().map(|&card| self.card_to_value(card)).collect(),
+            WildcardScope::HandTypeAndCards => substituted_cards.iter().map(|&card| self.card_to_value(card)).collect()
         }
     }
+}
+
+// Hands are classified by their multiset signature, how many of each card there are
+#[derive(PartialEq, Eq, Debug)]
+struct HandType {
+    signature: Vec<usize> // from the biggest group of equal cards
+}
 
-    fn get_with_joker(cards: &Vec<char>) -> Self {
-        let mut checked_values = HashSet::new();
-        checked_values.insert(&'J');
-        let jokers = cards.iter().filter(|&n| *n == 'J').count() as u32;
-        let mut pairs = 0;
-        let mut three_of_a_kinds = 0;
-        let mut four_of_a_kinds = 0;
+impl HandType {
+    fn new(cards: &[char]) -> Self {
+        let mut counts = HashMap::new();
         for card in cards {
-            if checked_values.contains(card) {
-                continue;
-            }
-            checked_values.insert(card);
-            match cards.iter().filter(|&n| *n == *card).count() {
-                2 => pairs += 1,
-                3 => three_of_a_kinds +=1,
-                4 => four_of_a_kinds += 1,
-                5 => return HandType::FiveOfAKind,
-                6 => return HandType::FiveOfAKind,
-                _ => continue
-            };
+            *counts.entry(card).or_insert(0) += 1;
         }
-        if four_of_a_kinds == 1 {
-            HandType::repetitions_to_type(4 + jokers)
+        let mut signature: Vec<usize> = counts.into_values().collect();
+        signature.sort_by_key(|&count| Reverse(count));
+        Self {signature}
+    }
+
+    fn parse(input: &str) -> Self {
+        let mut signature: Vec<usize> = input.chars().map(|count| count.to_digit(10).unwrap() as usize).collect();
+        signature.sort_by_key(|&count| Reverse(count));
+        Self {signature}
+    }
+
+    // all the ways to split size cards into groups of at most max_group cards, biggest groups first
+    fn get_all_signatures(size: usize, max_group: usize) -> Vec<Vec<usize>> {
+        if size == 0 {
+            return vec![vec![]];
         }
-        else if three_of_a_kinds == 1 && pairs == 1 {
-            HandType::FullHouse
-        } else if three_of_a_kinds == 1 {
-            HandType::repetitions_to_type(3 + jokers)
-        } else if pairs == 2 {
-            if jokers == 1 {
-                HandType::FullHouse
-            }
-            else {
-                HandType::TwoPairs
+        let mut signatures = vec![];
+        for group in (1..=min(size, max_group)).rev() {
+            for mut rest in HandType::get_all_signatures(size - group, group) {
+                rest.insert(0, group);
+                signatures.push(rest);
             }
-        } else if pairs == 1 {
-            HandType::repetitions_to_type(jokers + 2)
+        }
+        signatures
+    }
+
+    fn name(&self) -> String {
+        let is = |prefix: &[usize]| self.signature.starts_with(prefix) && self.signature[prefix.len()..].iter().all(|&count| count == 1);
+        if self.signature.iter().sum::<usize>() != 5 {
+            self.signature.iter().map(|count| count.to_string()).collect::<Vec<String>>().join("+")
+        } else if is(&[5]) {
+            String::from("five of a kind")
+        } else if is(&[4]) {
+            String::from("four of a kind")
+        } else if is(&[3, 2]) {
+            String::from("full house")
+        } else if is(&[3]) {
+            String::from("three of a kind")
+        } else if is(&[2, 2]) {
+            String::from("two pairs")
+        } else if is(&[2]) {
+            String::from("pair")
         } else {
-            HandType::repetitions_to_type(jokers + 1)
+            String::from("high card")
         }
     }
 }
 
-#[derive(PartialEq, Eq, Ord, Debug)]
+#[derive(Debug)]
 struct Hand {
     hand_type: HandType,
+    type_rank: usize, // position of the hand type in the ranking of the rules
     cards: Vec<char>,
+    substituted_cards: Vec<char>, // the cards with wild cards replaced by what they stand for
+    card_values: Vec<u32>, // used on a hand type tie, as given by the rules the hand was made with
     bid: u32
 }
 
 impl Hand {
-    fn new(input: &str) -> Self {
+    fn new(input: &str, rules: &CardRules) -> Self {
         let (cards, bid) = input.trim().split_once(' ').unwrap();
         let bid = bid.parse().unwrap();
         let cards: Vec<char> = cards.chars().collect();
-        let hand_type = HandType::get(&cards);
-        Self {
-            hand_type,
-            cards,
-            bid
+        if rules.hand_size.is_some_and(|hand_size| hand_size != cards.len()) {
+            panic!("Hand {} doesn't have {} cards", input, rules.hand_size.unwrap());
         }
-    }
-
-    fn new_with_joker(input: &str) -> Self {
-        let (cards, bid) = input.trim().split_once(' ').unwrap();
-        let bid = bid.parse().unwrap();
-        let cards: Vec<char> = cards.chars().collect();
-        let hand_type = HandType::get_with_joker(&cards);
+        let substituted_cards = rules.get_best_substitution(&cards);
+        let hand_type = HandType::new(&substituted_cards);
+        let type_rank = match rules.get_type_rank(&hand_type) {
+            Some(type_rank) => type_rank,
+            None => panic!("Hand {} can't be made into any ranked hand type, {} isn't ranked", input, hand_type.name())
+        };
+        let card_values = rules.get_card_values(&cards, &substituted_cards);
         Self {
             hand_type,
+            type_rank,
             cards,
+            substituted_cards,
+            card_values,
             bid
         }
     }
 }
+// Identical cards and bids are the only way for two hands to be equal, hands that only share
+// the type and card values are ordered by their cards and then by bid, so sorting is deterministic
+impl Ord for Hand {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.type_rank.cmp(&other.type_rank)
+            .then_with(|| self.card_values.cmp(&other.card_values))
+            .then_with(|| self.cards.cmp(&other.cards))
+            .then_with(|| self.bid.cmp(&other.bid))
+    }
+}
 
 impl PartialOrd for Hand {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.hand_type != other.hand_type {
-            Some(other.hand_type.cmp(&self.hand_type))
-        } else {
-            for (card1, card2) in self.cards.iter().zip(other.cards.iter()) {
-                if card1 == card2 {
-                    continue
-                }
-                let card1_value = card_to_value(card1);
-                let card2_value = card_to_value(card2);
-                return Some(card1_value.cmp(&card2_value));
-            }
-            return None;
-        }
+        Some(self.cmp(other))
     }
 }
 
-fn card_to_value(card: &char) -> u32 {
-        match card {
-            'T' => 10,
-            'J' => unsafe{if j_meaning == JMeaning::Jack {11} else {1}},
-            'Q' => 12,
-            'K' => 13,
-            'A' => 14,
-            number => number.to_string().parse().unwrap()
-        }
+impl PartialEq for Hand {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
 }
 
-fn solve_part_1(input: &str) -> u32 {
+impl Eq for Hand {}
+
+fn get_winnings(input: &str, rules: &CardRules) -> u32 {
     let mut hands = vec![];
     for line in input.lines() {
-        hands.push(Hand::new(line));
+        hands.push(Hand::new(line, rules));
     }
     hands.sort();
     let mut result = 0;
@@ -184,29 +282,140 @@ fn solve_part_1(input: &str) -> u32 {
     result
 }
 
-fn solve_part_2(input: &str) -> u32 {
-    unsafe{j_meaning = JMeaning::Joker};
-    let mut hands = vec![];
-    for line in input.lines() {
-        hands.push(Hand::new_with_joker(line));
+fn parse_hands(input: &str, rules: &CardRules) -> Vec<Hand> {
+    input.lines().map(|line| Hand::new(line, rules)).collect()
+}
+
+// rank of every hand, in input order, 1 is the weakest
+fn get_ranks(hands: &[Hand]) -> Vec<u32> {
+    let mut order: Vec<usize> = (0..hands.len()).collect();
+    order.sort_by(|&a, &b| hands[a].cmp(&hands[b]));
+    let mut ranks = vec![0; hands.len()];
+    for (rank, i) in (1..).zip(order) {
+        ranks[i] = rank;
     }
-    hands.sort();
-    let mut result = 0;
-    for (hand, order) in hands.iter().zip(1..=(hands.len() as u32)) {
-        result += hand.bid * order;
+    ranks
+}
+
+fn explain(input: &str, rules: &CardRules) -> String {
+    let hands = parse_hands(input, rules);
+    let ranks = get_ranks(&hands);
+    let mut order: Vec<usize> = (0..hands.len()).collect();
+    order.sort_by_key(|&i| ranks[i]);
+    let mut result = String::new();
+    for i in order {
+        let hand = &hands[i];
+        let substitution = if hand.substituted_cards == hand.cards { String::from("-") } else { hand.substituted_cards.iter().collect() };
+        result.push_str(&format!("{} as {}, {}, values {:?}, rank {}, bid {}, winnings {}\n",
+            hand.cards.iter().collect::<String>(), substitution, hand.hand_type.name(), hand.card_values, ranks[i], hand.bid, ranks[i] * hand.bid));
+    }
+    result
+}
+
+// hands that have a different rank under the jack and joker rules
+fn diff(input: &str) -> String {
+    let jack_hands = parse_hands(input, &CardRules::jacks());
+    let joker_hands = parse_hands(input, &CardRules::jokers());
+    let jack_ranks = get_ranks(&jack_hands);
+    let joker_ranks = get_ranks(&joker_hands);
+    let mut result = String::new();
+    for i in 0..jack_hands.len() {
+        if jack_ranks[i] != joker_ranks[i] {
+            result.push_str(&format!("{}: rank {} -> {} ({} -> {})\n", jack_hands[i].cards.iter().collect::<String>(),
+                jack_ranks[i], joker_ranks[i], jack_hands[i].hand_type.name(), joker_hands[i].hand_type.name()));
+        }
     }
     result
 }
 
+fn solve_part_1(input: &str) -> u32 {
+    get_winnings(input, &CardRules::jacks())
+}
+
+fn solve_part_2(input: &str) -> u32 {
+    get_winnings(input, &CardRules::jokers())
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let task_part = args[1].parse::<u32>().unwrap();
     let file_path = &args[2];
     let file_contents = fs::read_to_string(file_path).unwrap();
-    let result = match task_part {
-        1 => solve_part_1(&file_contents),
-        2 => solve_part_2(&file_contents),
+    match args[1].as_str() {
+        "explain" => {
+            // usage: explain <file> [jacks|jokers] [rule options]
+            let base = if args.get(3).is_some_and(|base| base == "jokers") { CardRules::jokers() } else { CardRules::jacks() };
+            print!("{}", explain(&file_contents, &CardRules::from_args(&args, base)));
+            return;
+        }
+        "diff" => {
+            print!("{}", diff(&file_contents));
+            return;
+        }
+        _ => {}
+    }
+    let result = match args[1].as_str() {
+        "1" => solve_part_1(&file_contents),
+        "2" => solve_part_2(&file_contents),
+        "custom" => get_winnings(&file_contents, &CardRules::from_args(&args, CardRules::jacks())),
         _ => 0
     };
     println!("{}", result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Generates hands with a simple linear congruential generator, so every run checks the same hands
+    fn generate_hands(rules: &CardRules, count: usize) -> Vec<Hand> {
+        let mut state: u64 = 2023;
+        let mut next = |bound: usize| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) as usize % bound
+        };
+        let mut hands = vec![];
+        for _ in 0..count {
+            // a small bid range and reusing the first cards often gives plenty of ties to check
+            let card_count = 1 + next(rules.card_order.len());
+            let cards: String = (0..rules.hand_size.unwrap_or(5)).map(|_| rules.card_order[next(card_count)]).collect();
+            hands.push(Hand::new(&format!("{} {}", cards, next(3)), rules));
+        }
+        hands
+    }
+
+    // Checks the laws of a total order on generated hands, returns descriptions of violations
+    fn check_ordering(rules: &CardRules, count: usize) -> Vec<String> {
+        let hands = generate_hands(rules, count);
+        let describe = |hand: &Hand| format!("{} {} ({})", hand.cards.iter().collect::<String>(), hand.bid, hand.hand_type.name());
+        let mut violations = vec![];
+        for a in &hands {
+            for b in &hands {
+                if a.cmp(b) != b.cmp(a).reverse() {
+                    violations.push(format!("antisymmetry: {} and {}", describe(a), describe(b)));
+                }
+                if (a.cmp(b) == Ordering::Equal) != (a.cards == b.cards && a.bid == b.bid) {
+                    violations.push(format!("equality: {} and {}", describe(a), describe(b)));
+                }
+                if a.cmp(b) != Ordering::Less {
+                    continue;
+                }
+                for c in &hands {
+                    if b.cmp(c) == Ordering::Less && a.cmp(c) != Ordering::Less {
+                        violations.push(format!("transitivity: {} < {} < {}", describe(a), describe(b), describe(c)));
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    #[test]
+    fn jacks_ordering_is_consistent() {
+        assert_eq!(check_ordering(&CardRules::jacks(), 150), Vec::<String>::new());
+    }
+
+    #[test]
+    fn jokers_ordering_is_consistent() {
+        assert_eq!(check_ordering(&CardRules::jokers(), 150), Vec::<String>::new());
+    }
+}
@@ -1,72 +1,15 @@
+use regex::Regex;
+use std::cmp::{min, Reverse};
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
+use std::process;
 
 struct Intersection {
     left: String,
     right: String
 }
 
-#[derive(Clone)]
-struct PathToZ {
-    to_first: u64,
-    distances_to_next: Vec<u64>,
-    // using cache like that takes a lot of memory
-    // known_distances: Vec<(u64, usize)> // distance, last step index
-    last_known_distance: (u64, usize) // distance, last step index
-}
-
-impl PathToZ {
-    fn new(start: &str, directions: &str, map: &HashMap<String, Intersection>) -> Self {
-        let mut current_position = start;
-        let mut steps = 0;
-        let mut first_found = false;
-        let mut z_visited = HashSet::new();
-        let mut to_first = 0;
-        let mut distances_to_next = vec![];
-        loop {
-            for direction in directions.chars() {
-                current_position = match direction {
-                    'L' => &map[current_position].left,
-                    'R' => &map[current_position].right,
-                    _ => panic!()
-                };
-                steps += 1;
-                if current_position.ends_with('Z') {
-                    if !first_found {
-                        first_found = true;
-                        to_first = steps;
-                    }
-                    else {
-                        distances_to_next.push(steps);
-                        let distances_to_next_len = distances_to_next.len();
-                        if !z_visited.insert(current_position) {
-                            // return PathToZ{to_first, distances_to_next, known_distances: vec![(to_first, distances_to_next_len - 1)]};
-                            return PathToZ{to_first, distances_to_next, last_known_distance: (to_first, distances_to_next_len - 1)};
-                        }
-                    }
-                    steps = 0;
-                }
-            }
-        }
-    }
-
-    fn reaches_z(&mut self, steps: u64) -> bool {
-        loop {
-            let last_distance = self.last_known_distance.0;
-            if steps < last_distance {
-                return false;
-            }
-            else if steps == last_distance {
-                return true;
-            }
-            let current_index = (self.last_known_distance.1 + 1) % self.distances_to_next.len();
-            // self.known_distances.push((last_distance + self.distances_to_next[current_index], current_index));
-            self.last_known_distance = (last_distance + self.distances_to_next[current_index], current_index);
-        }
-    }
-}
-
 fn parse_input(input: &str) -> (&str, HashMap<String, Intersection>) {
     let (directions, mapping_part) = input.split_once("\n\n").unwrap();
     let mut mapping = HashMap::new();
@@ -83,68 +26,364 @@ fn parse_input(input: &str) -> (&str, HashMap<String, Intersection>) {
     (directions, mapping)
 }
 
-fn count_steps(start: &str, finish: &str, directions: &str, map: &HashMap<String, Intersection>) -> u64 {
-    let mut current_position = start;
-    let mut steps = 0;
-    loop {
-        for direction in directions.chars() {
-            current_position = match direction {
-                'L' => &map[current_position].left,
-                'R' => &map[current_position].right,
-                _ => panic!()
+enum NodeSelector {
+    Exact(String),
+    Suffix(String),
+    Pattern(Regex),
+    List(Vec<String>)
+}
+
+impl NodeSelector {
+    // exact:<name>, suffix:<end of name>, regex:<pattern> or list:<name>,<name>...
+    fn new(input: &str) -> Self {
+        let (kind, value) = input.split_once(':').unwrap();
+        match kind {
+            "exact" => NodeSelector::Exact(value.to_string()),
+            "suffix" => NodeSelector::Suffix(value.to_string()),
+            "regex" => NodeSelector::Pattern(Regex::new(value).unwrap()),
+            "list" => NodeSelector::List(value.split(',').map(|name| name.to_string()).collect()),
+            _ => panic!("Unknown node selector")
+        }
+    }
+
+    fn matches(&self, node: &str) -> bool {
+        match self {
+            NodeSelector::Exact(name) => node == name,
+            NodeSelector::Suffix(suffix) => node.ends_with(suffix.as_str()),
+            NodeSelector::Pattern(pattern) => pattern.is_match(node),
+            NodeSelector::List(names) => names.iter().any(|name| name == node)
+        }
+    }
+}
+
+
+// The walk of a ghost only depends on its node and its position in the directions, so it always
+// ends up in a cycle. Steps are counted from the start, the goal has to be reached at least 1 step in.
+struct GhostCycle {
+    start: String,
+    cycle_start: u64, // steps before the walk enters the cycle
+    cycle_length: u64,
+    goals_before_cycle: Vec<u64>, // steps at which a goal is reached before the cycle
+    goals_in_cycle: Vec<u64> // steps at which a goal is reached in the first pass of the cycle, modulo cycle_length
+}
+
+impl GhostCycle {
+    // fails when the walk gets to a node that's not in the network, or there are no directions to follow
+    fn new(start: &str, directions: &str, map: &HashMap<String, Intersection>, is_goal: impl Fn(&str) -> bool) -> Result<Self, String> {
+        let directions: Vec<char> = directions.chars().collect();
+        if directions.is_empty() {
+            return Err(String::from("there are no directions to follow"));
+        }
+        let mut current_position = start;
+        let mut steps = 0;
+        let mut visited = HashMap::new(); // (node, direction index) to the first step it was visited at
+        let mut goals = vec![];
+        loop {
+            let state = (current_position, steps % directions.len() as u64);
+            if let Some(&cycle_start) = visited.get(&state) {
+                let cycle_length = steps - cycle_start;
+                let goals_before_cycle = goals.iter().copied().filter(|&goal| goal < cycle_start && goal > 0).collect();
+                let mut goals_in_cycle: Vec<u64> = goals.iter().copied().filter(|&goal| goal >= cycle_start).map(|goal| goal % cycle_length).collect();
+                goals_in_cycle.sort();
+                return Ok(GhostCycle {start: start.to_string(), cycle_start, cycle_length, goals_before_cycle, goals_in_cycle});
+            }
+            visited.insert(state, steps);
+            if is_goal(current_position) {
+                goals.push(steps);
+            }
+            let intersection = map.get(current_position)
+                .ok_or(format!("the ghost starting at {} gets to {}, which is not in the network", start, current_position))?;
+            current_position = match directions[(steps % directions.len() as u64) as usize] {
+                'L' => &intersection.left,
+                'R' => &intersection.right,
+                direction => return Err(format!("unknown direction {}", direction))
             };
             steps += 1;
-            if current_position == finish {
-                return steps;
+        }
+    }
+
+    fn reaches_goal(&self, steps: u128) -> bool {
+        if steps < self.cycle_start as u128 {
+            steps > 0 && self.goals_before_cycle.contains(&(steps as u64))
+        } else {
+            self.goals_in_cycle.contains(&((steps % self.cycle_length as u128) as u64))
+        }
+    }
+}
+
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) { // (gcd, x, y) with a * x + b * y = gcd
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (gcd, x, y) = extended_gcd(b, a % b);
+        (gcd, y, x - (a / b) * y)
+    }
+}
+
+enum StepsError {
+    NoSolution(Vec<String>), // the proof that no solution exists
+    Overflow(String)
+}
+
+enum CongruenceError {
+    Conflict(u128), // the congruences disagree modulo their gcd
+    Overflow
+}
+
+// Combines t = a (mod m) and t = b (mod n) into one congruence, the moduli don't have to be coprime
+fn combine_congruences((a, m): (u128, u128), (b, n): (u128, u128)) -> Result<(u128, u128), CongruenceError> {
+    let to_signed = |value: u128| i128::try_from(value).map_err(|_| CongruenceError::Overflow);
+    let (gcd, x, _) = extended_gcd(to_signed(m)?, to_signed(n)?);
+    let difference = to_signed(b)? - to_signed(a)?;
+    if difference % gcd != 0 {
+        return Err(CongruenceError::Conflict(gcd as u128));
+    }
+    let gcd = gcd as u128;
+    let lcm = (m / gcd).checked_mul(n).ok_or(CongruenceError::Overflow)?;
+    // a + m * k = b (mod n), so k = (b - a) / gcd * x (mod n / gcd)
+    let n_reduced = (n / gcd) as i128;
+    let k = ((difference / gcd as i128) % n_reduced).checked_mul(x % n_reduced).ok_or(CongruenceError::Overflow)?.rem_euclid(n_reduced) as u128;
+    let combined = m.checked_mul(k).and_then(|product| product.checked_add(a)).ok_or(CongruenceError::Overflow)?;
+    Ok((combined % lcm, lcm))
+}
+
+// Tries every combination of goals in the cycles, returns the smallest solution at least min_steps
+// and otherwise the reasons why no combination works
+fn solve_cycles(cycles: &[GhostCycle], min_steps: u128, combined: (u128, u128), ghost: usize, conflicts: &mut Vec<String>) -> Result<Option<u128>, StepsError> {
+    if ghost == cycles.len() {
+        let (remainder, modulus) = combined;
+        // the smallest number at least min_steps that gives the remainder
+        let below = min_steps % modulus;
+        let distance = if remainder >= below { remainder - below } else { remainder + (modulus - below) };
+        return min_steps.checked_add(distance).map(Some).ok_or(StepsError::Overflow(format!("the solution is more than {}", u128::MAX)));
+    }
+    let cycle = &cycles[ghost];
+    let mut best: Option<u128> = None;
+    for &goal in &cycle.goals_in_cycle {
+        let congruence = (goal as u128, cycle.cycle_length as u128);
+        match combine_congruences(combined, congruence) {
+            Ok(next) => {
+                if let Some(steps) = solve_cycles(cycles, min_steps, next, ghost + 1, conflicts)? {
+                    best = Some(best.map_or(steps, |best| min(best, steps)));
+                }
             }
+            Err(CongruenceError::Conflict(gcd)) => conflicts.push(format!("steps = {} (mod {}) from the ghosts before {} and steps = {} (mod {}) for {} disagree modulo {}",
+                combined.0, combined.1, cycle.start, congruence.0, congruence.1, cycle.start, gcd)),
+            Err(CongruenceError::Overflow) => return Err(StepsError::Overflow(format!("combining steps = {} (mod {}) from the ghosts before {} with steps = {} (mod {}) overflows",
+                combined.0, combined.1, cycle.start, congruence.0, congruence.1)))
         }
     }
+    Ok(best)
 }
 
-fn count_steps_multi_input(starts: Vec<String>, directions: &str, map: &HashMap<String, Intersection>) -> u64 {
-    let mut paths_to_z = HashMap::new();
-    for start in &starts {
-        paths_to_z.insert(start, PathToZ::new(start, directions, map));
+// Returns the proof that no solution exists instead, if that's the case
+fn count_steps(starts: &NodeSelector, goals: &NodeSelector, directions: &str, map: &HashMap<String, Intersection>) -> Result<u128, StepsError> {
+    let mut starts: Vec<&String> = map.keys().filter(|node| starts.matches(node)).collect();
+    starts.sort();
+    if starts.is_empty() {
+        return Err(StepsError::NoSolution(vec![String::from("no node matches the start selector")]));
+    }
+    let cycles: Vec<GhostCycle> = starts.iter()
+        .map(|start| GhostCycle::new(start, directions, map, |position| goals.matches(position)))
+        .collect::<Result<_, _>>()
+        .map_err(|problem| StepsError::NoSolution(vec![problem]))?;
+    let mut proof = vec![];
+    for cycle in &cycles {
+        if cycle.goals_before_cycle.is_empty() && cycle.goals_in_cycle.is_empty() {
+            proof.push(format!("the ghost starting at {} never reaches a goal", cycle.start));
+        }
+    }
+    if !proof.is_empty() {
+        return Err(StepsError::NoSolution(proof));
+    }
+    // before every ghost is in its cycle, only the goals of the ghost with the longest way into it are candidates
+    let all_in_cycle = cycles.iter().map(|cycle| cycle.cycle_start).max().unwrap().max(1) as u128;
+    let latest = cycles.iter().max_by_key(|cycle| cycle.cycle_start).unwrap();
+    for &steps in &latest.goals_before_cycle {
+        if cycles.iter().all(|cycle| cycle.reaches_goal(steps as u128)) {
+            return Ok(steps as u128);
+        }
+    }
+    if !latest.goals_before_cycle.is_empty() {
+        proof.push(format!("none of the goals {} reaches before step {} is shared by all ghosts", latest.start, all_in_cycle));
+    }
+    let without_goals_in_cycle: Vec<String> = cycles.iter().filter(|cycle| cycle.goals_in_cycle.is_empty())
+        .map(|cycle| format!("the ghost starting at {} has no goals after step {}", cycle.start, cycle.cycle_start.saturating_sub(1)))
+        .collect();
+    if !without_goals_in_cycle.is_empty() {
+        proof.extend(without_goals_in_cycle);
+        return Err(StepsError::NoSolution(proof));
+    }
+    match solve_cycles(&cycles, all_in_cycle, (0, 1), 0, &mut proof)? {
+        Some(steps) => Ok(steps),
+        None => Err(StepsError::NoSolution(proof))
     }
-    
-    let reference_distance = (*paths_to_z.values().next().unwrap()).clone();
-    let mut steps: u64 = reference_distance.to_first;
-    loop {
-        for distance_to_next in &reference_distance.distances_to_next {
-            let mut all_good = true;
-            for (_, path_to_z) in &mut paths_to_z {
-                if !path_to_z.reaches_z(steps) {
-                    all_good = false;
-                    break;
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn network_to_dot(map: &HashMap<String, Intersection>) -> String {
+    let mut nodes: Vec<&String> = map.keys().collect();
+    nodes.sort();
+    let mut result = String::from("digraph network {\n");
+    for node in nodes {
+        result.push_str(&format!("    \"{}\" -> \"{}\" [label=\"L\", color=blue];\n", escape_dot(node), escape_dot(&map[node].left)));
+        result.push_str(&format!("    \"{}\" -> \"{}\" [label=\"R\", color=red, style=dashed];\n", escape_dot(node), escape_dot(&map[node].right)));
+    }
+    result.push_str("}\n");
+    result
+}
+
+fn get_neighbours<'a>(node: &str, map: &'a HashMap<String, Intersection>) -> Vec<&'a String> {
+    match map.get(node) {
+        Some(intersection) => vec![&intersection.left, &intersection.right].into_iter().filter(|next| map.contains_key(*next)).collect(),
+        None => vec![]
+    }
+}
+
+fn get_reachable<'a>(start: &'a String, map: &'a HashMap<String, Intersection>) -> HashSet<&'a String> {
+    let mut reachable = HashSet::from([start]);
+    let mut to_visit = vec![start];
+    while let Some(node) = to_visit.pop() {
+        for next in get_neighbours(node, map) {
+            if reachable.insert(next) {
+                to_visit.push(next);
+            }
+        }
+    }
+    reachable
+}
+
+// Kosaraju's algorithm, components are sorted from the biggest one
+fn get_strongly_connected_components(map: &HashMap<String, Intersection>) -> Vec<Vec<&String>> {
+    let mut nodes: Vec<&String> = map.keys().collect();
+    nodes.sort();
+    // first pass: nodes ordered by when their depth first search finishes
+    let mut finished = vec![];
+    let mut visited = HashSet::new();
+    for &node in &nodes {
+        if !visited.insert(node) {
+            continue;
+        }
+        let mut stack = vec![(node, 0)];
+        while let Some((current, next_index)) = stack.pop() {
+            let neighbours = get_neighbours(current, map);
+            if next_index < neighbours.len() {
+                stack.push((current, next_index + 1));
+                if visited.insert(neighbours[next_index]) {
+                    stack.push((neighbours[next_index], 0));
                 }
+            } else {
+                finished.push(current);
             }
-            if all_good {
-                return steps;
+        }
+    }
+    // second pass: depth first searches on the reversed graph, from the last finished node
+    let mut reversed: HashMap<&String, Vec<&String>> = HashMap::new();
+    for &node in &nodes {
+        for next in get_neighbours(node, map) {
+            reversed.entry(next).or_default().push(node);
+        }
+    }
+    let mut components = vec![];
+    let mut assigned = HashSet::new();
+    for &node in finished.iter().rev() {
+        if !assigned.insert(node) {
+            continue;
+        }
+        let mut component = vec![node];
+        let mut to_visit = vec![node];
+        while let Some(current) = to_visit.pop() {
+            for &previous in reversed.get(current).into_iter().flatten() {
+                if assigned.insert(previous) {
+                    component.push(previous);
+                    to_visit.push(previous);
+                }
             }
-            steps += distance_to_next;
+        }
+        component.sort();
+        components.push(component);
+    }
+    components.sort_by_key(|component| Reverse(component.len()));
+    components
+}
+
+fn analyze(starts: &NodeSelector, goals: &NodeSelector, directions: &str, map: &HashMap<String, Intersection>) -> String {
+    let mut result = String::new();
+    let components = get_strongly_connected_components(map);
+    result.push_str(&format!("{} strongly connected components\n", components.len()));
+    for component in &components {
+        let component_starts: Vec<&str> = component.iter().filter(|node| starts.matches(node)).map(|node| node.as_str()).collect();
+        let component_goals: Vec<&str> = component.iter().filter(|node| goals.matches(node)).map(|node| node.as_str()).collect();
+        if component.len() > 1 || !component_starts.is_empty() || !component_goals.is_empty() {
+            result.push_str(&format!("component of {} nodes, starts {:?}, goals {:?}\n", component.len(), component_starts, component_goals));
+        }
+    }
+    let mut start_nodes: Vec<&String> = map.keys().filter(|node| starts.matches(node)).collect();
+    start_nodes.sort();
+    for start in start_nodes {
+        let reachable = get_reachable(start, map);
+        let mut reachable_goals: Vec<&str> = reachable.iter().filter(|node| goals.matches(node)).map(|node| node.as_str()).collect();
+        reachable_goals.sort();
+        result.push_str(&format!("{}: reaches {} nodes, goals {:?}\n", start, reachable.len(), reachable_goals));
+        match GhostCycle::new(start, directions, map, |node| goals.matches(node)) {
+            Ok(cycle) => result.push_str(&format!("{}: cycle starts after {} steps, period {} steps ({} times the directions), goals before cycle {:?}, goals in cycle at {:?} modulo period\n",
+                start, cycle.cycle_start, cycle.cycle_length, cycle.cycle_length / directions.len() as u64, cycle.goals_before_cycle, cycle.goals_in_cycle)),
+            Err(problem) => result.push_str(&format!("{}: {}\n", start, problem))
         }
     }
+    result
 }
 
-fn solve_part_1(directions: &str, map: &HashMap<String, Intersection>) -> u64 {
-    count_steps("AAA", "ZZZ", directions, map)
+fn solve_part_1(directions: &str, map: &HashMap<String, Intersection>) -> Result<u128, StepsError> {
+    count_steps(&NodeSelector::Exact(String::from("AAA")), &NodeSelector::Exact(String::from("ZZZ")), directions, map)
 }
 
-fn solve_part_2(directions: &str, map: &HashMap<String, Intersection>) -> u64 {
-    let starts = map.keys().filter(|position| position.ends_with('A')).map(|s| s.clone()).collect();
-    count_steps_multi_input(starts, directions, map)
+fn solve_part_2(directions: &str, map: &HashMap<String, Intersection>) -> Result<u128, StepsError> {
+    count_steps(&NodeSelector::Suffix(String::from("A")), &NodeSelector::Suffix(String::from("Z")), directions, map)
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let task_part = args[1].parse::<u64>().unwrap();
     let file_path = &args[2];
     let file_contents = fs::read_to_string(file_path).unwrap();
     let (directions, map) = parse_input(&file_contents);
-    let result = match task_part {
-        1 => solve_part_1(directions, &map),
-        2 => solve_part_2(directions, &map),
-        _ => 0
+    let get_selector = |name: &str, default: &str| NodeSelector::new(args.iter().find_map(|arg| arg.strip_prefix(name)).unwrap_or(default));
+    match args[1].as_str() {
+        "dot" => {
+            print!("{}", network_to_dot(&map));
+            return;
+        }
+        "analyze" => {
+            // usage: analyze <file> [--start=<selector>] [--goal=<selector>]
+            print!("{}", analyze(&get_selector("--start=", "suffix:A"), &get_selector("--goal=", "suffix:Z"), directions, &map));
+            return;
+        }
+        _ => {}
+    }
+    let result = match args[1].as_str() {
+        "1" => solve_part_1(directions, &map),
+        "2" => solve_part_2(directions, &map),
+        "custom" => {
+            // usage: custom <file> [--start=<selector>] [--goal=<selector>]
+            count_steps(&get_selector("--start=", "suffix:A"), &get_selector("--goal=", "suffix:Z"), directions, &map)
+        }
+        _ => Ok(0)
     };
-    println!("{}", result);
+    match result {
+        Ok(steps) => println!("{}", steps),
+        Err(StepsError::NoSolution(proof)) => {
+            eprintln!("No solution:");
+            for reason in proof {
+                eprintln!("{}", reason);
+            }
+            process::exit(1);
+        }
+        Err(StepsError::Overflow(problem)) => {
+            eprintln!("Can't count the steps: {}", problem);
+            process::exit(1);
+        }
+    }
 }
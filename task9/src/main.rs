@@ -1,7 +1,9 @@
 use std::env;
+use std::fmt;
 use std::fs;
+use std::process;
 
-fn parse_input(input: &str) -> Vec<Vec<i32>> {
+fn parse_input(input: &str) -> Vec<Vec<i128>> {
     let mut result = vec![];
     for line in input.lines() {
         let mut series = vec![];
@@ -13,7 +15,7 @@ fn parse_input(input: &str) -> Vec<Vec<i32>> {
     result
 }
 
-fn calculate_difference(series: &Vec<i32>) -> (Vec<i32>, bool) { // (difference, is it constant?)
+fn calculate_difference(series: &[i128]) -> (Vec<i128>, bool) { // (difference, is it constant?)
     let mut result = vec![];
     let mut constant = true;
     let first_difference = series[1] - series[0];
@@ -29,57 +31,234 @@ fn calculate_difference(series: &Vec<i32>) -> (Vec<i32>, bool) { // (difference,
     (result, constant)
 }
 
-fn calculate_next(series: &Vec<i32>) -> i32 {
-    let mut differences = vec![series.clone()];
-    loop {
-        let (new_difference, constant) = calculate_difference(differences.last().unwrap());
-        differences.push(new_difference);
-        if constant {
-            break;
+// Rows of differences down to the first constant one, with the degree of the polynomial,
+// which is None when the differences run out before becoming constant
+fn get_difference_pyramid(series: &[i128]) -> (Vec<Vec<i128>>, Option<usize>) {
+    let mut pyramid = vec![series.to_vec()];
+    let mut constant = series.len() >= 2 && series.iter().all(|&number| number == series[0]);
+    while !constant {
+        if pyramid.last().unwrap().len() <= 2 {
+            return (pyramid, None);
         }
+        let (new_difference, new_constant) = calculate_difference(pyramid.last().unwrap());
+        pyramid.push(new_difference);
+        constant = new_constant;
     }
-    differences.iter().fold(0, |sum, vector| {sum + vector.last().unwrap()})
+    let degree = pyramid.len() - 1;
+    (pyramid, Some(degree))
 }
 
-fn calculate_previous(series: &Vec<i32>) -> i32 {
-    let mut differences = vec![series.clone()];
-    loop {
-        let (new_difference, constant) = calculate_difference(differences.last().unwrap());
-        differences.push(new_difference);
-        if constant {
-            break;
+// The pyramid of the series extended by the given number of steps on both sides,
+// values depending on extrapolated numbers are in brackets
+fn render_pyramid(series: &[i128], steps: usize) -> String {
+    let (pyramid, degree) = get_difference_pyramid(series);
+    let Some(degree) = degree else {
+        let mut result = String::from("not a polynomial, nothing to extrapolate\n");
+        for row in &pyramid {
+            result += &format!("{:?}\n", row);
         }
+        return result;
+    };
+    let fit = PolynomialFit::new(series).unwrap();
+    let start = -(steps as i128);
+    let end = series.len() as i128 + steps as i128;
+    let extended: Vec<i128> = match (start..end).map(|x| fit.evaluate(x)).collect() {
+        Ok(extended) => extended,
+        Err(error) => return format!("degree {}, {}\n", degree, error)
+    };
+    let mut rows = vec![extended];
+    for _ in 0..degree {
+        rows.push(calculate_difference(rows.last().unwrap()).0);
+    }
+    // an entry in row r at index i is computed from the extended series i..=i + r
+    let is_original = |r: usize, i: usize| i >= steps && i + r < steps + series.len();
+    let mut cells = vec![];
+    for (r, row) in rows.iter().enumerate() {
+        cells.push(row.iter().enumerate().map(|(i, number)| {
+            if is_original(r, i) { format!("{}", number) } else { format!("[{}]", number) }
+        }).collect::<Vec<String>>());
     }
-    let mut even = false;
-    differences.iter().skip(1).fold(*differences[0].first().unwrap(), |previous, vector| {even = !even; if even {previous - vector.first().unwrap()} else {previous + vector.first().unwrap()}})
+    let mut width = cells.iter().flatten().map(|cell| cell.len()).max().unwrap() + 1;
+    width += width % 2;
+    let mut result = format!("degree {}\n", degree);
+    for (r, row) in cells.iter().enumerate() {
+        result += &" ".repeat(r * width / 2);
+        for cell in row {
+            result += &format!("{:>width$}", cell, width = width);
+        }
+        result += "\n";
+    }
+    result
 }
 
-fn solve_part_1(series_vector: &Vec<Vec<i32>>) -> i32 {
-    let mut result = 0;
-    for series in series_vector {
-        result += calculate_next(&series);
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Rational {
+    numerator: i128,
+    denominator: i128 // always positive
+}
+
+impl Rational {
+    fn new(numerator: i128, denominator: i128) -> Self {
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        Self {numerator: numerator / divisor, denominator: denominator / divisor}
+    }
+
+    fn checked_add(self, other: Rational) -> Option<Rational> {
+        let numerator = self.numerator.checked_mul(other.denominator)?.checked_add(other.numerator.checked_mul(self.denominator)?)?;
+        Some(Rational::new(numerator, self.denominator.checked_mul(other.denominator)?))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
     }
-    result
 }
 
-fn solve_part_2(series_vector: &Vec<Vec<i32>>) -> i32 {
-    let mut result = 0;
+// The polynomial going through the series, with the series starting at x = 0
+struct PolynomialFit {
+    newton_coefficients: Vec<i128> // first value of every row of differences, p(x) = sum of c_k * binomial(x, k)
+}
+
+impl PolynomialFit {
+    // Fails when no row of differences is constant, then any polynomial of the highest
+    // degree fits the series and there's nothing to confirm that it's the right one
+    fn new(series: &[i128]) -> Result<Self, String> {
+        match get_difference_pyramid(series) {
+            (pyramid, Some(_)) => Ok(Self {newton_coefficients: pyramid.iter().map(|row| row[0]).collect()}),
+            (_, None) => Err(format!("{:?} is not a polynomial of a degree lower than {}", series, series.len().saturating_sub(1)))
+        }
+    }
+
+    fn degree(&self) -> usize {
+        self.newton_coefficients.len() - 1
+    }
+
+    fn evaluate(&self, x: i128) -> Result<i128, String> {
+        let overflow = || format!("overflow evaluating the polynomial at {}", x);
+        let mut result: i128 = 0;
+        let mut binomial: i128 = 1; // binomial(x, k), also defined for negative x
+        for (k, coefficient) in (0..).zip(&self.newton_coefficients) {
+            // only computed when there's a coefficient for it, so it can't overflow needlessly
+            if k > 0 {
+                binomial = x.checked_sub(k - 1).and_then(|factor| binomial.checked_mul(factor)).ok_or_else(overflow)? / k;
+            }
+            result = coefficient.checked_mul(binomial).and_then(|term| result.checked_add(term)).ok_or_else(overflow)?;
+        }
+        Ok(result)
+    }
+
+    // coefficients of x^0, x^1, x^2...
+    fn get_coefficients(&self) -> Result<Vec<Rational>, String> {
+        let overflow = || String::from("overflow computing the coefficients");
+        let mut coefficients = vec![Rational::new(0, 1); self.newton_coefficients.len()];
+        let mut falling_factorial: Vec<i128> = vec![1]; // x (x - 1) ... (x - k + 1), with integer coefficients
+        let mut factorial: i128 = 1;
+        for (k, &newton_coefficient) in (0_i128..).zip(&self.newton_coefficients) {
+            for (power, &falling_coefficient) in falling_factorial.iter().enumerate() {
+                let term = Rational::new(newton_coefficient.checked_mul(falling_coefficient).ok_or_else(overflow)?, factorial);
+                coefficients[power] = coefficients[power].checked_add(term).ok_or_else(overflow)?;
+            }
+            // multiply by (x - k)
+            let mut next: Vec<i128> = vec![0; falling_factorial.len() + 1];
+            for (power, &falling_coefficient) in falling_factorial.iter().enumerate() {
+                next[power + 1] = next[power + 1].checked_add(falling_coefficient).ok_or_else(overflow)?;
+                next[power] = k.checked_mul(falling_coefficient).and_then(|product| next[power].checked_sub(product)).ok_or_else(overflow)?;
+            }
+            falling_factorial = next;
+            factorial = factorial.checked_mul(k + 1).ok_or_else(overflow)?;
+        }
+        Ok(coefficients)
+    }
+
+    fn closed_form(&self) -> Result<String, String> {
+        let mut terms = vec![];
+        for (power, coefficient) in self.get_coefficients()?.iter().enumerate().rev() {
+            if coefficient.numerator == 0 {
+                continue;
+            }
+            terms.push(match power {
+                0 => format!("{}", coefficient),
+                1 => format!("{} x", coefficient),
+                _ => format!("{} x^{}", coefficient, power)
+            });
+        }
+        if terms.is_empty() {
+            Ok(String::from("0"))
+        } else {
+            Ok(terms.join(" + ").replace("+ -", "- "))
+        }
+    }
+}
+
+// positive steps go past the end of the series, negative ones before its start
+fn extrapolate(series: &[i128], steps: i128) -> Result<i128, String> {
+    let fit = PolynomialFit::new(series)?;
+    let x = if steps > 0 { (series.len() as i128 - 1).checked_add(steps).ok_or("overflow")? } else { steps };
+    fit.evaluate(x)
+}
+
+fn solve(series_vector: &[Vec<i128>], steps: i128) -> Result<i128, String> {
+    let mut result: i128 = 0;
     for series in series_vector {
-        result += calculate_previous(&series);
+        result = result.checked_add(extrapolate(series, steps)?).ok_or("overflow adding up the extrapolated values")?;
     }
-    result
+    Ok(result)
+}
+
+fn solve_part_1(series_vector: &[Vec<i128>]) -> Result<i128, String> {
+    solve(series_vector, 1)
+}
+
+fn solve_part_2(series_vector: &[Vec<i128>]) -> Result<i128, String> {
+    solve(series_vector, -1)
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let task_part = args[1].parse::<u64>().unwrap();
     let file_path = &args[2];
     let file_contents = fs::read_to_string(file_path).unwrap();
     let series_vector = parse_input(&file_contents);
-    let result = match task_part {
-        1 => solve_part_1(&series_vector),
-        2 => solve_part_2(&series_vector),
-        _ => 0
+    let result = match args[1].as_str() {
+        "fit" => {
+            for series in &series_vector {
+                match PolynomialFit::new(series) {
+                    Ok(fit) => match fit.closed_form() {
+                        Ok(closed_form) => println!("degree {}: {}", fit.degree(), closed_form),
+                        Err(error) => println!("degree {}: {}", fit.degree(), error)
+                    },
+                    Err(error) => println!("{}", error)
+                }
+            }
+            return;
+        }
+        // usage: pyramid <file> [steps], extrapolated values are in brackets
+        "pyramid" => {
+            let steps = args.get(3).map_or(1, |steps| steps.parse().unwrap());
+            for series in &series_vector {
+                println!("{}", render_pyramid(series, steps));
+            }
+            return;
+        }
+        // usage: extrapolate <file> <steps>, negative steps go backwards
+        "extrapolate" => solve(&series_vector, args[3].parse().unwrap()),
+        "1" => solve_part_1(&series_vector),
+        "2" => solve_part_2(&series_vector),
+        _ => Ok(0)
     };
-    println!("{}", result);
+    match result {
+        Ok(result) => println!("{}", result),
+        Err(error) => {
+            eprintln!("Can't extrapolate: {}", error);
+            process::exit(1);
+        }
+    }
 }